@@ -0,0 +1,33 @@
+/*
+    web-server [地址] [文档根目录]
+
+    默认监听 127.0.0.1:7878，GET / 返回当前目录下的 hello.html，其余返回 404.html；
+    指定文档根目录时改为提供这个目录下的静态文件，没有 index.html 的目录列出内容
+*/
+use std::env;
+use std::process;
+
+use rust_learning::web_server::{Router, Server, StaticFiles};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    let mut server = Server::bind(&addr).unwrap_or_else(|e| {
+        eprintln!("failed to bind {addr}: {e}");
+        process::exit(1);
+    });
+    if let Some(root) = args.next() {
+        let files = StaticFiles::new(&root).unwrap_or_else(|e| {
+            eprintln!("cannot serve {root}: {e}");
+            process::exit(1);
+        });
+        let router = Router::new().get("/*path", files.directory_listing(true).handler("path"));
+        server = server.root(root).router(router);
+    }
+    // 绑定端口 0 时由系统分配端口，打印出来供调用方连接
+    println!("Listening on http://{}", server.local_addr().unwrap());
+    if let Err(e) = server.run() {
+        eprintln!("server error: {e}");
+        process::exit(1);
+    }
+}
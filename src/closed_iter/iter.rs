@@ -34,6 +34,14 @@ fn shoes_in_size(shoes: Vec<Shoe>, shoe_size: u32) -> Vec<Shoe> {
 mod tests {
     use std::iter::Iterator;
     use super::*;
+    use crate::thread_pool::ParallelSlice;
+    use crate::ThreadPool;
+
+    // 并行版本：在线程池上过滤，借用 shoes 而不是获取所有权，结果的顺序和顺序版本一样
+    fn shoes_in_size_par<'a>(pool: &ThreadPool, shoes: &'a [Shoe], shoe_size: u32) -> Vec<&'a Shoe> {
+        shoes.par_filter(pool, |s| s.size == shoe_size)
+    }
+
     #[test]
     fn test_iter_def() {
         iter_def()
@@ -74,6 +82,19 @@ mod tests {
         assert_eq!(v2, vec![2, 3, 4]);
     }
 
+    #[test]
+    fn test_par_sum_and_map() {
+        // 同样的计算分别顺序执行和在线程池上并行执行，结果一样
+        let pool = ThreadPool::new(4);
+        let v1: Vec<i32> = (1..=100).collect();
+
+        let total: i32 = v1.iter().sum();
+        assert_eq!(v1.par_reduce(&pool, |a, b| a + b), Some(total));
+
+        let v2: Vec<_> = v1.iter().map(|x| x + 1).collect();
+        assert_eq!(v1.par_map(&pool, |x| x + 1), v2);
+    }
+
     #[test]
     fn test_iterator_closure() {
         // 很多迭代器适配器接受闭包作为参数，而我们通常会指定捕获其环境的闭包作为迭代器适配器的参数。
@@ -113,4 +134,30 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn filters_by_size_in_parallel() {
+        let pool = ThreadPool::new(2);
+        let shoes: Vec<Shoe> = [10, 13, 10, 9, 10]
+            .into_iter()
+            .enumerate()
+            .map(|(i, size)| Shoe {
+                size,
+                style: format!("style {i}"),
+            })
+            .collect();
+
+        let parallel: Vec<&Shoe> = shoes_in_size_par(&pool, &shoes, 10);
+        let sequential = shoes_in_size(
+            shoes
+                .iter()
+                .map(|s| Shoe {
+                    size: s.size,
+                    style: s.style.clone(),
+                })
+                .collect(),
+            10,
+        );
+        assert_eq!(parallel, sequential.iter().collect::<Vec<_>>());
+    }
 }
\ No newline at end of file
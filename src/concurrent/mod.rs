@@ -8,6 +8,8 @@ use std::thread;
 use std::time::{Duration, Instant};
 use trpl::{Either, Html};
 
+use crate::ThreadPool;
+
 /// 并发编程
 pub fn concur_demo() {
     // 并发编程（Concurrent programming），代表程序的不同部分相互独立地执行，
@@ -251,18 +253,22 @@ fn page_title_2(url: &str) -> impl Future<Output = Option<String>> + '_ {
 }
 
 pub fn async_await_2() {
-    trpl::run(async {
+    // 不用 trpl::run，而是在我们自己的线程池上运行：block_on 在当前线程上运行 future，spawn_future 把 future 交给 Worker。
+    let pool = Arc::new(ThreadPool::new(2));
+    pool.block_on(async {
         // rpl crate 提供了一个 spawn_task 函数，它看起来非常像 thread::spawn API，和一个 sleep 函数，这是 thread::sleep API 的异步版本。
-        let handle = trpl::spawn_task(async {
+        // 线程池的 spawn_future 和 sleep 对应它们；被 spawn 的 future 必须是 'static 的，所以要把线程池的 Arc 移进去。
+        let tasks = Arc::clone(&pool);
+        let handle = pool.spawn_future(async move {
             for i in 1..10 {
                 println!("hi number {i} from the first task!");
-                trpl::sleep(Duration::from_millis(500)).await;
+                tasks.sleep(Duration::from_millis(500)).await;
             }
         });
 
         for i in 1..5 {
             println!("hi number {i} from the second task!");
-            trpl::sleep(Duration::from_millis(500)).await;
+            pool.sleep(Duration::from_millis(500)).await;
         }
         // 对于线程来说，可以使用 join 方法来 “阻塞” 直到线程结束运行。
         // 我们可以使用 await 来实现相同的效果，因为任务句柄本身是一个 future。它的 Output 类型是一个 Result，所以我们还需要 unwrap 来 await 它。
@@ -272,18 +278,19 @@ pub fn async_await_2() {
 }
 
 pub fn async_await_3() {
-    trpl::run(async {
+    let pool = ThreadPool::new(2);
+    pool.block_on(async {
         let fut1 = async {
             for i in 1..10 {
                 println!("hi number {i} from the first task!");
-                trpl::sleep(Duration::from_millis(500)).await;
+                pool.sleep(Duration::from_millis(500)).await;
             }
         };
 
         let fut2 = async {
             for i in 1..5 {
                 println!("hi number {i} from the second task!");
-                trpl::sleep(Duration::from_millis(500)).await;
+                pool.sleep(Duration::from_millis(500)).await;
             }
         };
         // 这里，你每次都会看到完全相同的顺序，这与我们在线程中看到的情况非常不同。这是因为 trpl::join 函数是 公平的（fair），
@@ -294,7 +301,8 @@ pub fn async_await_3() {
 }
 
 pub fn async_await_4() {
-    trpl::run(async {
+    let pool = ThreadPool::new(2);
+    pool.block_on(async {
         let (tx, mut rx) = trpl::channel();
 
         let val = String::from("hi");
@@ -306,7 +314,8 @@ pub fn async_await_4() {
 }
 
 pub fn async_await_5() {
-    trpl::run(async {
+    let pool = ThreadPool::new(2);
+    pool.block_on(async {
         let (tx, mut rx) = trpl::channel();
 
         let vals = vec![
@@ -318,7 +327,7 @@ pub fn async_await_5() {
 
         for val in vals {
             tx.send(val).unwrap();
-            trpl::sleep(Duration::from_millis(500)).await;
+            pool.sleep(Duration::from_millis(500)).await;
         }
         // while let 循环是我们在第六章中见过的 if let 结构的循环版本。只要其指定的模式持续匹配循环就会一直执行。
         // rx.recv 调用产生一个 Future，我们会 await 它。运行时会暂停 Future 直到它就绪。一旦消息到达，future 会解析为 Some(message)，
@@ -334,8 +343,11 @@ pub fn async_await_5() {
 }
 
 pub fn async_await_6() {
-    trpl::run(async {
+    let pool = ThreadPool::new(2);
+    pool.block_on(async {
         let (tx, mut rx) = trpl::channel();
+        // async move 只应该拿走 tx，线程池还是借用
+        let pool = &pool;
         // 不加move时，tx的所有权没有传递给rx_fut，所以程序无法结束
         let tx_fut = pin!(async move {
             let vals = vec![
@@ -349,7 +361,7 @@ pub fn async_await_6() {
                 // 目前发送消息的异步代码块只是借用了 tx，因为发送消息并不需要其所有权
                 //  move 关键字也能像闭包那样作用于异步代码块。
                 tx.send(val).unwrap();
-                trpl::sleep(Duration::from_millis(500)).await;
+                pool.sleep(Duration::from_millis(500)).await;
             }
         });
 
@@ -387,16 +399,17 @@ pub fn async_await_6() {
 }
 
 pub fn async_await_7() {
-    trpl::run(async {
+    let pool = ThreadPool::new(2);
+    pool.block_on(async {
         let slow = async {
             println!("'slow' started.");
-            trpl::sleep(Duration::from_millis(100)).await;
+            pool.sleep(Duration::from_millis(100)).await;
             println!("'slow' finished.");
         };
 
         let fast = async {
             println!("'fast' started.");
-            trpl::sleep(Duration::from_millis(50)).await;
+            pool.sleep(Duration::from_millis(50)).await;
             println!("'fast' finished.");
         };
         // futures 传递给 trpl::race，它返回一个值表明哪个传递的 future 最先返回。
@@ -412,12 +425,13 @@ pub fn async_await_7() {
 }
 
 pub fn async_await_yielding() {
-    trpl::run(async {
+    let pool = ThreadPool::new(2);
+    pool.block_on(async {
         let one_ns = Duration::from_nanos(1);
         let start = Instant::now();
         async {
             for _ in 1..1000 {
-                trpl::sleep(one_ns).await;
+                pool.sleep(one_ns).await;
             }
         }
         .await;
@@ -454,13 +468,14 @@ fn slow(name: &str, ms: u64) {
 }
 
 pub fn async_await_timeout() {
-    trpl::run(async {
+    let pool = ThreadPool::new(2);
+    pool.block_on(async {
         let slow = async {
-            trpl::sleep(Duration::from_secs(5)).await;
+            pool.sleep(Duration::from_secs(5)).await;
             "Finally finished"
         };
 
-        match timeout(slow, Duration::from_secs(2)).await {
+        match timeout(&pool, slow, Duration::from_secs(2)).await {
             Ok(message) => println!("Succeeded with '{message}'"),
             Err(duration) => {
                 println!("Failed after {} seconds", duration.as_secs())
@@ -469,8 +484,12 @@ pub fn async_await_timeout() {
     });
 }
 
-async fn timeout<F: Future>(future_to_try: F, max_time: Duration) -> Result<F::Output, Duration> {
-    match trpl::race(future_to_try, trpl::sleep(max_time)).await {
+async fn timeout<F: Future>(
+    pool: &ThreadPool,
+    future_to_try: F,
+    max_time: Duration,
+) -> Result<F::Output, Duration> {
+    match trpl::race(future_to_try, pool.sleep(max_time)).await {
         Either::Left(output) => Ok(output),
         Either::Right(_) => Err(max_time),
     }
@@ -1,7 +1,3 @@
-use std::sync::mpsc::Receiver;
-use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
-
 pub mod advanced_features;
 pub mod closed_iter;
 pub mod collect;
@@ -15,82 +11,11 @@ pub mod panic;
 pub mod pub_use;
 pub mod smart_point;
 pub mod struct_def;
+pub mod thread_pool;
 pub mod type_trait_life;
+pub mod web_server;
 
-struct Worker {
-    id: usize,
-    thread: Option<thread::JoinHandle<()>>,
-}
-
-impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv();
-
-            match message {
-                Ok(job) => {
-                    println!("Worker {id} got a job; executing.");
-
-                    job();
-                }
-                Err(_) => {
-                    println!("Worker {id} disconnected; shutting down.");
-                    break;
-                }
-            }
-        });
-
-        Worker {
-            id,
-            thread: Some(thread),
-        }
-    }
-}
-
-pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: Option<mpsc::Sender<Job>>,
-}
-// struct Job;
-
-type Job = Box<dyn FnOnce() + Send + 'static>;
-impl ThreadPool {
-    pub fn new(size: usize) -> ThreadPool {
-        assert!(size > 0);
-        let (sender, receiver) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(receiver));
-        let mut workers = Vec::with_capacity(size);
-
-        for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
-        }
-
-        ThreadPool { workers, sender: Some(sender) }
-    }
-
-    //闭包作为参数时可以使用三个不同的 trait：Fn、FnMut 和 FnOnce。
-    pub fn execute<F>(&self, f: F)
-    where
-        F: FnOnce() + Send + 'static,
-    {
-        let job = Box::new(f);
-
-        self.sender.as_ref().unwrap().send(job).unwrap();
-    }
-}
-
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
-        drop(self.sender.take());
-        for worker in &mut self.workers {
-            println!("Shutting down worker {}", worker.id);
-            // 如果 Worker 存放的是 Option<thread::JoinHandle<()>，就可以在 Option 上调用 take 方法将值从 Some 成员中移动出来而对 None 成员不做处理。
-            if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
-            }
-        }
-    }
-}
+pub use thread_pool::ThreadPool;
 
 pub mod unit_test {
     fn add(a: i32, b: i32) -> i32 {
@@ -0,0 +1,143 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::Shared;
+
+impl Shared {
+    /// 在每个在岗的 Worker 上执行一次 f，结果按 Worker id 排列
+    ///
+    /// 任务放进各个 Worker 自己的队列，不会被别的 Worker 窃取；排到之前 Worker 不会退休。
+    /// 确定性线程池没有 Worker，f 在调用方线程上以 id 0 执行一次。
+    pub(crate) fn broadcast<F, T>(self: &Arc<Self>, f: F) -> Vec<T>
+    where
+        F: Fn(usize) -> T + Sync,
+        T: Send,
+    {
+        if self.deterministic.is_some() {
+            return vec![f(0)];
+        }
+        let results = Mutex::new(Vec::new());
+        self.scope(|s| {
+            let (f, results) = (&f, &results);
+            self.for_each_live_worker(|id| {
+                s.spawn_pinned(id, move || {
+                    let value = f(id);
+                    results
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push((id, value));
+                });
+            });
+        });
+        let mut results = results.into_inner().unwrap_or_else(PoisonError::into_inner);
+        results.sort_by_key(|(id, _)| *id);
+        results.into_iter().map(|(_, value)| value).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ThreadPool;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn runs_once_on_every_worker() {
+        let pool = ThreadPool::builder()
+            .num_threads(4)
+            .thread_name("bc")
+            .build()
+            .unwrap();
+        let calls = AtomicUsize::new(0);
+        let names = pool.broadcast(|id| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            (id, thread::current().name().unwrap().to_string())
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            names,
+            (0..4)
+                .map(|id| (id, format!("bc-{id}")))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn busy_workers_are_not_skipped() {
+        let pool = ThreadPool::new(2);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        // 空闲的 Worker 不能替忙着的 Worker 执行
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            release_tx.send(()).unwrap();
+        });
+        let threads: HashSet<_> = pool
+            .broadcast(|_| thread::current().id())
+            .into_iter()
+            .collect();
+        assert_eq!(threads.len(), 2);
+    }
+
+    #[test]
+    fn broadcast_from_inside_a_worker() {
+        let pool = Arc::new(ThreadPool::new(2));
+        let inner = Arc::clone(&pool);
+        let ids = pool.submit(move || inner.broadcast(|id| id)).join();
+        assert_eq!(ids, Ok(vec![0, 1]));
+    }
+
+    #[test]
+    fn worker_state_is_reused_per_worker() {
+        let pool = ThreadPool::builder()
+            .num_threads(3)
+            .with_worker_state(Vec::<usize>::new)
+            .build()
+            .unwrap();
+        for i in 0..30 {
+            pool.execute_with_state(move |seen: &mut Vec<usize>| seen.push(i))
+                .unwrap();
+        }
+        pool.run_until_idle();
+        let mut all: Vec<usize> = pool
+            .broadcast_with_state(|_, seen: &mut Vec<usize>| std::mem::take(seen))
+            .into_iter()
+            .flatten()
+            .collect();
+        all.sort();
+        assert_eq!(all, (0..30).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn worker_state_need_not_be_send() {
+        use std::rc::Rc;
+        let pool = ThreadPool::builder()
+            .num_threads(2)
+            .with_worker_state(|| Rc::new(thread::current().id()))
+            .build()
+            .unwrap();
+        let owners = pool.broadcast_with_state(|_, owner: &mut Rc<thread::ThreadId>| {
+            **owner == thread::current().id()
+        });
+        assert_eq!(owners, vec![true, true]);
+    }
+
+    #[test]
+    #[should_panic(expected = "worker state is")]
+    fn wrong_state_type_panics_on_submit() {
+        let pool = ThreadPool::builder()
+            .num_threads(1)
+            .with_worker_state(|| 0u32)
+            .build()
+            .unwrap();
+        let _ = pool.execute_with_state(|_: &mut String| {});
+    }
+}
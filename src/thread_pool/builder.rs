@@ -0,0 +1,341 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::deterministic::Deterministic;
+use super::keyed::KeyedQueues;
+use super::scheduler::Scheduler;
+use super::sizing::Sizing;
+use super::stats::Metrics;
+use super::timer::Timer;
+use super::watchdog::Watchdog;
+use super::worker_state::WorkerState;
+use super::{EventSink, JobPanic, NullSink, OverflowPolicy, PanicHandler, Shared, ThreadPool};
+
+type ThreadHook = Arc<dyn Fn(usize) + Send + Sync + 'static>;
+
+/// 创建线程池失败的原因
+#[derive(Debug)]
+pub enum PoolCreationError {
+    /// Worker 数量必须大于 0
+    ZeroThreads,
+    /// 有界队列的容量必须大于 0
+    ZeroCapacity,
+    /// 最少 Worker 数大于最多 Worker 数
+    MinAboveMax,
+    /// 操作系统拒绝创建线程
+    Spawn(io::Error),
+}
+
+impl fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolCreationError::ZeroThreads => write!(f, "thread pool needs at least one worker"),
+            PoolCreationError::ZeroCapacity => write!(f, "bounded job queue needs a capacity"),
+            PoolCreationError::MinAboveMax => {
+                write!(f, "minimum worker count exceeds the maximum")
+            }
+            PoolCreationError::Spawn(err) => write!(f, "failed to spawn worker thread: {err}"),
+        }
+    }
+}
+
+impl Error for PoolCreationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PoolCreationError::ZeroThreads
+            | PoolCreationError::ZeroCapacity
+            | PoolCreationError::MinAboveMax => None,
+            PoolCreationError::Spawn(err) => Some(err),
+        }
+    }
+}
+
+/// Worker 线程的配置，补建 Worker 时也要用到
+pub(crate) struct Config {
+    pub(crate) thread_name: Option<String>,
+    pub(crate) stack_size: Option<usize>,
+    pub(crate) on_thread_start: Option<ThreadHook>,
+    pub(crate) on_thread_stop: Option<ThreadHook>,
+    pub(crate) worker_state: Option<Arc<WorkerState>>,
+}
+
+/// 线程池的构建器
+pub struct ThreadPoolBuilder {
+    min_threads: usize,
+    max_threads: usize,
+    keep_alive: Option<Duration>,
+    bound: Option<(usize, OverflowPolicy)>,
+    aging: u64,
+    stall_threshold: Option<Duration>,
+    seed: Option<u64>,
+    config: Config,
+    panic_handler: PanicHandler,
+    event_sink: Arc<dyn EventSink>,
+}
+
+impl ThreadPoolBuilder {
+    pub fn new() -> ThreadPoolBuilder {
+        ThreadPoolBuilder {
+            min_threads: 4,
+            max_threads: 4,
+            keep_alive: None,
+            bound: None,
+            aging: 16,
+            stall_threshold: None,
+            seed: None,
+            config: Config {
+                thread_name: None,
+                stack_size: None,
+                on_thread_start: None,
+                on_thread_stop: None,
+                worker_state: None,
+            },
+            panic_handler: Arc::new(|panic: JobPanic| {
+                eprintln!(
+                    "Worker {} panicked while running job {}: {}",
+                    panic.worker_id, panic.job_id, panic.message
+                );
+            }),
+            event_sink: Arc::new(NullSink),
+        }
+    }
+
+    /// 固定的 Worker 数量，默认 4
+    pub fn num_threads(mut self, num_threads: usize) -> ThreadPoolBuilder {
+        self.min_threads = num_threads;
+        self.max_threads = num_threads;
+        self
+    }
+
+    /// 最少保留的 Worker 数，创建时就启动这么多
+    pub fn min_threads(mut self, min_threads: usize) -> ThreadPoolBuilder {
+        self.min_threads = min_threads;
+        self
+    }
+
+    /// 任务积压时最多扩容到的 Worker 数
+    pub fn max_threads(mut self, max_threads: usize) -> ThreadPoolBuilder {
+        self.max_threads = max_threads;
+        self
+    }
+
+    /// 空闲超过 keep_alive 的 Worker 退休，直到只剩 min_threads 个；默认不退休
+    pub fn keep_alive(mut self, keep_alive: Duration) -> ThreadPoolBuilder {
+        self.keep_alive = Some(keep_alive);
+        self
+    }
+
+    /// 排队的任务最多 capacity 个，超出时按 policy 处理；默认不限
+    pub fn bounded(mut self, capacity: usize, policy: OverflowPolicy) -> ThreadPoolBuilder {
+        self.bound = Some((capacity, policy));
+        self
+    }
+
+    /// 全局队列里的 Normal、Low 任务等待超过 aging 次出队后被提前处理，默认 16
+    pub fn priority_aging(mut self, aging: u64) -> ThreadPoolBuilder {
+        self.aging = aging;
+        self
+    }
+
+    /// 任务执行超过 threshold 时发出 PoolEvent::StallDetected，每个任务只报告一次；默认不检测
+    pub fn stall_threshold(mut self, threshold: Duration) -> ThreadPoolBuilder {
+        self.stall_threshold = Some(threshold);
+        self
+    }
+
+    /// 确定性模式，见 ThreadPool::deterministic；Worker 数量和有界队列的设置被忽略
+    pub fn deterministic(mut self, seed: u64) -> ThreadPoolBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// 线程名前缀，线程名为 "{prefix}-{worker id}"
+    pub fn thread_name(mut self, prefix: impl Into<String>) -> ThreadPoolBuilder {
+        self.config.thread_name = Some(prefix.into());
+        self
+    }
+
+    /// 每个 Worker 线程的栈大小（字节）
+    pub fn stack_size(mut self, size: usize) -> ThreadPoolBuilder {
+        self.config.stack_size = Some(size);
+        self
+    }
+
+    /// Worker 线程启动后、取任务之前调用，参数是 Worker id
+    pub fn on_thread_start<H>(mut self, hook: H) -> ThreadPoolBuilder
+    where
+        H: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_thread_start = Some(Arc::new(hook));
+        self
+    }
+
+    /// Worker 线程退出前调用，包括因 panic 退出的情况
+    pub fn on_thread_stop<H>(mut self, hook: H) -> ThreadPoolBuilder
+    where
+        H: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_thread_stop = Some(Arc::new(hook));
+        self
+    }
+
+    /// 每个 Worker 持有一个 init 创建的状态，execute_with_state 提交的任务可以修改它
+    ///
+    /// 状态在 Worker 第一次执行需要它的任务时创建，之后一直属于这个 Worker 线程，所以不要求 Send。
+    pub fn with_worker_state<S, I>(mut self, init: I) -> ThreadPoolBuilder
+    where
+        S: 'static,
+        I: Fn() -> S + Send + Sync + 'static,
+    {
+        self.config.worker_state = Some(Arc::new(WorkerState::new(init)));
+        self
+    }
+
+    /// 任务 panic 时的处理函数，默认实现只是打印到标准错误
+    pub fn panic_handler<H>(mut self, handler: H) -> ThreadPoolBuilder
+    where
+        H: Fn(JobPanic) + Send + Sync + 'static,
+    {
+        self.panic_handler = Arc::new(handler);
+        self
+    }
+
+    /// 线程池事件交给谁处理，默认 NullSink 什么都不做；传入 StdoutSink 打印到标准输出
+    pub fn event_sink<S>(mut self, sink: S) -> ThreadPoolBuilder
+    where
+        S: EventSink + 'static,
+    {
+        self.event_sink = Arc::new(sink);
+        self
+    }
+
+    pub fn build(mut self) -> Result<ThreadPool, PoolCreationError> {
+        if self.seed.is_some() {
+            self.min_threads = 0;
+            self.max_threads = 0;
+            self.bound = None;
+        } else if self.max_threads == 0 {
+            return Err(PoolCreationError::ZeroThreads);
+        }
+        if self.min_threads > self.max_threads {
+            return Err(PoolCreationError::MinAboveMax);
+        }
+        if let Some((0, _)) = self.bound {
+            return Err(PoolCreationError::ZeroCapacity);
+        }
+        let shared = Arc::new(Shared {
+            scheduler: Scheduler::new(self.bound, self.aging),
+            sizing: Sizing::new(self.min_threads, self.max_threads, self.keep_alive),
+            timer: Arc::new(Timer::new()),
+            watchdog: Watchdog::new(self.stall_threshold),
+            deterministic: self.seed.map(Deterministic::new),
+            keyed: KeyedQueues::default(),
+            next_job_id: AtomicU64::new(0),
+            metrics: Metrics::default(),
+            workers: Mutex::new(Vec::with_capacity(self.min_threads)),
+            panic_handler: self.panic_handler,
+            event_sink: self.event_sink,
+            config: self.config,
+        });
+
+        let pool = ThreadPool { shared };
+        // 创建失败时 pool 被 drop，已经启动的 Worker 会被正常关闭
+        pool.shared
+            .start_workers()
+            .map_err(PoolCreationError::Spawn)?;
+        pool.shared
+            .start_watchdog()
+            .map_err(PoolCreationError::Spawn)?;
+
+        Ok(pool)
+    }
+}
+
+impl Default for ThreadPoolBuilder {
+    fn default() -> Self {
+        ThreadPoolBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn zero_threads_is_an_error() {
+        let result = ThreadPoolBuilder::new().num_threads(0).build();
+        assert!(matches!(result, Err(PoolCreationError::ZeroThreads)));
+    }
+
+    #[test]
+    fn min_above_max_is_an_error() {
+        let result = ThreadPoolBuilder::new()
+            .min_threads(3)
+            .max_threads(2)
+            .build();
+        assert!(matches!(result, Err(PoolCreationError::MinAboveMax)));
+    }
+
+    #[test]
+    fn threads_are_named() {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(1)
+            .thread_name("pool")
+            .build()
+            .unwrap();
+        let name = pool.submit(|| thread::current().name().map(String::from));
+        assert_eq!(name.join().unwrap().as_deref(), Some("pool-0"));
+    }
+
+    #[test]
+    fn stack_size_allows_deep_recursion() {
+        fn depth(n: u64) -> u64 {
+            let buf = [n as u8; 1024];
+            if n == 0 {
+                0
+            } else {
+                std::hint::black_box(&buf);
+                1 + depth(n - 1)
+            }
+        }
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(1)
+            .stack_size(64 * 1024 * 1024)
+            .build()
+            .unwrap();
+        assert_eq!(pool.submit(|| depth(10_000)).join(), Ok(10_000));
+    }
+
+    #[test]
+    fn start_and_stop_hooks() {
+        let (tx, rx) = mpsc::channel();
+        let stop_tx = tx.clone();
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(3)
+            .on_thread_start(move |id| tx.send(("start", id)).unwrap())
+            .on_thread_stop(move |id| stop_tx.send(("stop", id)).unwrap())
+            .build()
+            .unwrap();
+        drop(pool);
+
+        let mut events: Vec<_> = rx.try_iter().collect();
+        events.sort();
+        assert_eq!(
+            events,
+            vec![
+                ("start", 0),
+                ("start", 1),
+                ("start", 2),
+                ("stop", 0),
+                ("stop", 1),
+                ("stop", 2)
+            ]
+        );
+    }
+}
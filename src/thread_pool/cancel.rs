@@ -0,0 +1,128 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// 取消标记，克隆出来的副本共享同一个状态
+///
+/// 排队中的任务被取消后不会再执行；正在执行的任务需要自己检查 is_cancelled 并提前返回。
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thread_pool::JobError;
+    use crate::ThreadPool;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    // 占住唯一的 Worker，返回放行用的发送端
+    fn block_worker(pool: &ThreadPool) -> mpsc::Sender<()> {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        release_tx
+    }
+
+    #[test]
+    fn clones_share_state() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+    }
+
+    #[test]
+    fn queued_job_is_skipped() {
+        let pool = ThreadPool::new(1);
+        let release = block_worker(&pool);
+        let token = CancellationToken::new();
+        let ran = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ran);
+        let handle = pool.submit_cancellable(&token, move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let other = pool.submit(|| 7);
+        token.cancel();
+        release.send(()).unwrap();
+
+        assert_eq!(handle.join(), Err(JobError::Cancelled));
+        assert_eq!(other.join(), Ok(7));
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn running_job_polls_token() {
+        let pool = ThreadPool::new(1);
+        let token = CancellationToken::new();
+        let (started_tx, started_rx) = mpsc::channel();
+        let handle = pool.submit_cancellable(&token, move |token| {
+            started_tx.send(()).unwrap();
+            let mut rounds = 0;
+            while !token.is_cancelled() {
+                rounds += 1;
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            rounds
+        });
+        started_rx.recv().unwrap();
+        token.cancel();
+        // 已经开始执行的任务照常返回结果
+        assert!(handle.join().is_ok());
+    }
+
+    #[test]
+    fn panicking_job_reports_panic() {
+        let pool = ThreadPool::new(1);
+        let token = CancellationToken::new();
+        let handle = pool.submit_cancellable(&token, |_| panic!("boom"));
+        assert_eq!(handle.join(), Err(JobError::Panicked(String::from("boom"))));
+        // Worker 没有因为 panic 退出
+        assert_eq!(pool.submit(|| 1).join(), Ok(1));
+    }
+
+    #[test]
+    fn cancel_all_drops_pending_jobs() {
+        let pool = ThreadPool::new(1);
+        let release = block_worker(&pool);
+        let handles: Vec<_> = (0..10).map(|i| pool.submit(move || i)).collect();
+        let token = CancellationToken::new();
+        let (tx, rx) = mpsc::channel::<()>();
+        pool.execute_cancellable(&token, move |_| tx.send(()).unwrap())
+            .unwrap();
+
+        assert_eq!(pool.cancel_all(), 11);
+        // 排队的任务立即被丢弃，不用等 Worker 空出来
+        for handle in handles {
+            assert_eq!(handle.join(), Err(JobError::Cancelled));
+        }
+        assert_eq!(rx.recv(), Err(mpsc::RecvError));
+        assert!(!token.is_cancelled());
+
+        // 之后提交的任务不受影响
+        release.send(()).unwrap();
+        assert_eq!(pool.submit(|| 1).join(), Ok(1));
+    }
+}
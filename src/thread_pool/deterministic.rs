@@ -0,0 +1,310 @@
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::Waker;
+use std::time::Instant;
+
+use super::job_handle::{self, Completer, JobHandle};
+use super::{Job, Priority, Shared};
+
+/// SplitMix64：状态只有一个 u64，同一个种子总是得到同一串数
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// 确定性模式：没有 Worker 线程，任务只在调用方线程上按种子决定的顺序执行
+///
+/// 每次从最高优先级的排队任务里用随机数挑一个，同样的种子、同样的提交顺序得到同样的执行顺序。
+pub(crate) struct Deterministic {
+    pub(crate) seed: u64,
+    state: Mutex<DeterministicState>,
+}
+
+struct DeterministicState {
+    rng: SplitMix64,
+    queue: Vec<(Priority, Job)>,
+    // 队列空着时等待新任务的 block_on，定时器线程把任务排进来时叫醒它们
+    waiting: Vec<Waker>,
+}
+
+impl Deterministic {
+    pub(crate) fn new(seed: u64) -> Deterministic {
+        Deterministic {
+            seed,
+            state: Mutex::new(DeterministicState {
+                rng: SplitMix64(seed),
+                queue: Vec::new(),
+                waiting: Vec::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, DeterministicState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn push(&self, job: Job, priority: Priority) {
+        let waiting = {
+            let mut state = self.lock();
+            state.queue.push((priority, job));
+            std::mem::take(&mut state.waiting)
+        };
+        for waker in waiting {
+            waker.wake();
+        }
+    }
+
+    /// 队列为空时登记 waker，下一个任务排进来时唤醒它；队列不空时返回 false，不登记
+    pub(crate) fn wake_on_push(&self, waker: &Waker) -> bool {
+        let mut state = self.lock();
+        if !state.queue.is_empty() {
+            return false;
+        }
+        state.waiting.push(waker.clone());
+        true
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().queue.len()
+    }
+
+    /// 按种子挑出下一个任务
+    fn pop(&self) -> Option<Job> {
+        let mut state = self.lock();
+        let top = state.queue.iter().map(|(priority, _)| *priority).min()?;
+        let candidates: Vec<usize> = (0..state.queue.len())
+            .filter(|&i| state.queue[i].0 == top)
+            .collect();
+        let pick = candidates[(state.rng.next() % candidates.len() as u64) as usize];
+        // remove 而不是 swap_remove：其余任务的相对顺序不变，便于从日志推断
+        Some(state.queue.remove(pick).1)
+    }
+
+    pub(crate) fn remove_cancellable(&self) -> Vec<Job> {
+        let mut state = self.lock();
+        let (keep, removed) = std::mem::take(&mut state.queue)
+            .into_iter()
+            .partition(|(_, job)| job.must_run);
+        state.queue = keep;
+        removed.into_iter().map(|(_, job)| job).collect()
+    }
+}
+
+impl Shared {
+    /// 新任务的句柄；确定性线程池的句柄 join 时自己执行排队的任务，否则会一直等下去
+    pub(crate) fn handle_pair<T>(self: &Arc<Self>) -> (JobHandle<T>, Completer<T>) {
+        let (handle, completer) = job_handle::pair();
+        if self.deterministic.is_none() {
+            return (handle, completer);
+        }
+        let shared = Arc::downgrade(self);
+        let drive = Arc::new(move || {
+            shared
+                .upgrade()
+                .is_some_and(|shared| shared.run_next_deterministic())
+        });
+        (handle.with_drive(drive), completer)
+    }
+
+    /// 确定性模式下在当前线程上执行下一个任务，没有排队的任务时返回 false
+    pub(crate) fn run_next_deterministic(&self) -> bool {
+        let Some(job) = self.deterministic.as_ref().and_then(Deterministic::pop) else {
+            return false;
+        };
+        // 确定性模式没有 Worker，事件和统计都记在 0 号上
+        self.run_job(0, job);
+        true
+    }
+
+    /// 执行到没有排队的任务为止，返回这期间执行完的任务数
+    ///
+    /// 普通线程池上等到队列为空、所有 Worker 都空闲。
+    pub(crate) fn run_until_idle(&self) -> usize {
+        let before = self.metrics.completed.load(Ordering::SeqCst);
+        if self.deterministic.is_some() {
+            while self.run_next_deterministic() {}
+        } else {
+            while self.scheduler.len() > 0 || self.scheduler.idle_workers() < self.sizing.live() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+        (self.metrics.completed.load(Ordering::SeqCst) - before) as usize
+    }
+
+    /// 关闭时按 Drain 执行剩下的任务，过了 deadline 的部分丢弃，返回丢弃的数量
+    pub(crate) fn drain_deterministic(&self, deadline: Instant) -> usize {
+        while Instant::now() < deadline && self.run_next_deterministic() {}
+        self.deterministic
+            .as_ref()
+            .map_or(0, |deterministic| deterministic.remove_cancellable().len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_pool::{CancellationToken, Priority, ShutdownMode};
+    use crate::ThreadPool;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    fn order(seed: u64) -> Vec<u32> {
+        let pool = ThreadPool::deterministic(seed);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..20 {
+            let seen = Arc::clone(&seen);
+            pool.execute(move || seen.lock().unwrap().push(i)).unwrap();
+        }
+        assert_eq!(pool.run_until_idle(), 20);
+        let order = seen.lock().unwrap().clone();
+        order
+    }
+
+    #[test]
+    fn same_seed_same_order() {
+        assert_eq!(order(42), order(42));
+        let mut sorted = order(42);
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+        // 不同的种子几乎不可能得到同一个排列
+        assert_ne!(order(42), order(7));
+    }
+
+    #[test]
+    fn jobs_only_run_inside_run_until_idle() {
+        let pool = ThreadPool::deterministic(1);
+        assert_eq!(pool.num_workers(), 0);
+        let mut handle = pool.submit(|| std::thread::current().id());
+        assert_eq!(handle.try_join(), None);
+        assert_eq!(pool.stats().queue_depth, 1);
+        pool.run_until_idle();
+        assert_eq!(handle.try_join(), Some(Ok(std::thread::current().id())));
+    }
+
+    #[test]
+    fn nested_jobs_and_priorities() {
+        let pool = Arc::new(ThreadPool::deterministic(3));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..3 {
+            let (inner, seen) = (Arc::clone(&pool), Arc::clone(&seen));
+            pool.execute_with_priority(Priority::Low, move || {
+                seen.lock().unwrap().push(format!("low {i}"));
+                let seen = Arc::clone(&seen);
+                inner
+                    .execute_with_priority(Priority::High, move || {
+                        seen.lock().unwrap().push(format!("high {i}"));
+                    })
+                    .unwrap();
+            })
+            .unwrap();
+        }
+        assert_eq!(pool.run_until_idle(), 6);
+        // 每个 Low 任务提交的 High 任务总是紧接着执行
+        let seen = seen.lock().unwrap();
+        for pair in seen.chunks(2) {
+            assert_eq!(pair[0].replace("low", "high"), pair[1]);
+        }
+    }
+
+    #[test]
+    fn scope_keyed_and_futures_work_without_threads() {
+        let pool = ThreadPool::deterministic(9);
+        let mut totals = [0; 4];
+        pool.scope(|s| {
+            for (i, total) in totals.iter_mut().enumerate() {
+                s.spawn(move || *total = i * 10);
+            }
+        });
+        assert_eq!(totals, [0, 10, 20, 30]);
+
+        let keyed = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..5 {
+            let keyed = Arc::clone(&keyed);
+            pool.execute_keyed("k", move || keyed.lock().unwrap().push(i))
+                .unwrap();
+        }
+        pool.run_until_idle();
+        assert_eq!(*keyed.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+
+        let value = pool.block_on(async {
+            let a = pool.spawn_future(async { 2 });
+            let b = pool.spawn_future(async { 3 });
+            a.await.unwrap() * b.await.unwrap()
+        });
+        assert_eq!(value, 6);
+    }
+
+    #[test]
+    fn join_runs_queued_jobs() {
+        let pool = Arc::new(ThreadPool::deterministic(11));
+        assert_eq!(pool.submit(|| 1 + 1).join(), Ok(2));
+
+        // 任务里再提交任务并 join，嵌套执行
+        let inner = Arc::clone(&pool);
+        let outer = pool.submit(move || inner.submit(|| 3).join().unwrap() * 2);
+        let mut other = pool.submit(|| "other");
+        assert_eq!(outer.join(), Ok(6));
+        assert_eq!(
+            other.join_timeout(Duration::from_secs(5)),
+            Some(Ok("other"))
+        );
+    }
+
+    #[test]
+    fn spawned_futures_can_sleep() {
+        let pool = ThreadPool::deterministic(13);
+        let sleep = pool.sleep(Duration::from_millis(20));
+        let value = pool.block_on(async {
+            let slept = pool.spawn_future(async move {
+                sleep.await;
+                7
+            });
+            slept.await.unwrap()
+        });
+        assert_eq!(value, 7);
+
+        let sleep = pool.sleep(Duration::from_millis(20));
+        let started = Instant::now();
+        let handle = pool.spawn_future(async move {
+            sleep.await;
+            8
+        });
+        assert_eq!(handle.join(), Ok(8));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn run_until_idle_waits_for_threaded_pool() {
+        let pool = ThreadPool::new(2);
+        assert_eq!(pool.seed(), None);
+        for _ in 0..6 {
+            pool.execute(|| std::thread::sleep(Duration::from_millis(5)))
+                .unwrap();
+        }
+        assert_eq!(pool.run_until_idle(), 6);
+        assert_eq!(pool.stats().queue_depth, 0);
+    }
+
+    #[test]
+    fn cancel_and_shutdown() {
+        let pool = ThreadPool::deterministic(5);
+        let token = CancellationToken::new();
+        let cancelled = pool.submit_cancellable(&token, |_| ());
+        token.cancel();
+        let dropped = pool.submit(|| ());
+        assert_eq!(pool.cancel_all(), 2);
+        assert!(dropped.join().is_err());
+        assert!(cancelled.join().is_err());
+
+        let ran = pool.submit(|| 1);
+        let report = pool.shutdown(ShutdownMode::Drain, Instant::now() + Duration::from_secs(5));
+        assert_eq!(ran.join(), Ok(1));
+        assert_eq!(report.discarded, 0);
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// 线程池内部发生的事情，交给 EventSink 处理
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolEvent {
+    WorkerStarted {
+        worker_id: usize,
+    },
+    JobStarted {
+        worker_id: usize,
+        job_id: u64,
+    },
+    JobFinished {
+        worker_id: usize,
+        job_id: u64,
+        elapsed: Duration,
+        panicked: bool,
+    },
+    /// 任务在执行前被取消
+    JobSkipped {
+        worker_id: usize,
+        job_id: u64,
+    },
+    /// Worker 因为空闲超时（idle 为 true）或者线程池缩容而退休
+    WorkerRetired {
+        worker_id: usize,
+        idle: bool,
+    },
+    /// Worker 收到关闭通知后退出
+    WorkerShutdown {
+        worker_id: usize,
+    },
+    /// 线程池关闭时开始等待 Worker 退出
+    JoiningWorker {
+        worker_id: usize,
+    },
+    /// 到了关闭的截止时间 Worker 还没退出，不再等待
+    WorkerDetached {
+        worker_id: usize,
+    },
+    /// Worker 线程因为 panic 退出，随后会补上一个新的
+    WorkerDied {
+        worker_id: usize,
+    },
+    /// 创建 Worker 线程失败；worker_id 为 None 表示扩容时失败
+    SpawnFailed {
+        worker_id: Option<usize>,
+        error: String,
+    },
+    /// 任务执行时间超过了看门狗的阈值，Worker 可能卡住了
+    StallDetected {
+        worker_id: usize,
+        job_id: u64,
+        elapsed: Duration,
+        job_label: Option<String>,
+    },
+    /// 定期导出指标失败
+    StatsExportFailed {
+        error: String,
+    },
+}
+
+impl fmt::Display for PoolEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolEvent::WorkerStarted { worker_id } => write!(f, "Worker {worker_id} started."),
+            PoolEvent::JobStarted { worker_id, .. } => {
+                write!(f, "Worker {worker_id} got a job; executing.")
+            }
+            PoolEvent::JobFinished {
+                worker_id,
+                job_id,
+                elapsed,
+                panicked,
+            } => {
+                let outcome = if *panicked { "panicked" } else { "finished" };
+                write!(
+                    f,
+                    "Worker {worker_id} {outcome} job {job_id} in {elapsed:?}."
+                )
+            }
+            PoolEvent::JobSkipped { worker_id, job_id } => {
+                write!(f, "Worker {worker_id} skipped cancelled job {job_id}.")
+            }
+            PoolEvent::WorkerRetired {
+                worker_id,
+                idle: true,
+            } => write!(f, "Worker {worker_id} has been idle; retiring."),
+            PoolEvent::WorkerRetired {
+                worker_id,
+                idle: false,
+            } => write!(f, "Worker {worker_id} is no longer needed; retiring."),
+            PoolEvent::WorkerShutdown { worker_id } => {
+                write!(f, "Worker {worker_id} disconnected; shutting down.")
+            }
+            PoolEvent::JoiningWorker { worker_id } => write!(f, "Shutting down worker {worker_id}"),
+            PoolEvent::WorkerDetached { worker_id } => {
+                write!(f, "Worker {worker_id} did not stop in time; detaching.")
+            }
+            PoolEvent::WorkerDied { worker_id } => {
+                write!(f, "Worker {worker_id} died; spawning a replacement.")
+            }
+            PoolEvent::SpawnFailed {
+                worker_id: Some(worker_id),
+                error,
+            } => write!(f, "Failed to respawn worker {worker_id}: {error}"),
+            PoolEvent::SpawnFailed {
+                worker_id: None,
+                error,
+            } => write!(f, "Failed to grow thread pool: {error}"),
+            PoolEvent::StallDetected {
+                worker_id,
+                job_id,
+                elapsed,
+                job_label,
+            } => {
+                write!(f, "Worker {worker_id} has been running job {job_id}")?;
+                if let Some(label) = job_label {
+                    write!(f, " ({label})")?;
+                }
+                write!(f, " for {elapsed:?}; it may be stuck.")
+            }
+            PoolEvent::StatsExportFailed { error } => {
+                write!(f, "Failed to export thread pool stats: {error}")
+            }
+        }
+    }
+}
+
+/// 接收线程池事件，会在 Worker 线程上被并发调用，实现里不要阻塞太久
+pub trait EventSink: Send + Sync {
+    fn event(&self, event: &PoolEvent);
+}
+
+impl<S: EventSink + ?Sized> EventSink for Arc<S> {
+    fn event(&self, event: &PoolEvent) {
+        (**self).event(event)
+    }
+}
+
+/// 丢弃所有事件，是线程池的默认 sink
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullSink;
+
+impl EventSink for NullSink {
+    fn event(&self, _event: &PoolEvent) {}
+}
+
+/// 打印到标准输出，需要时通过 ThreadPoolBuilder::event_sink 打开
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn event(&self, event: &PoolEvent) {
+        println!("{event}");
+    }
+}
+
+/// 把事件存在内存里，方便测试里断言
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    events: Mutex<Vec<PoolEvent>>,
+}
+
+impl MemorySink {
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+
+    /// 到目前为止收到的所有事件
+    pub fn events(&self) -> Vec<PoolEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// 取出并清空已经收到的事件
+    pub fn take(&self) -> Vec<PoolEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl EventSink for MemorySink {
+    fn event(&self, event: &PoolEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(event.clone());
+    }
+}
+
+/// 把事件发到通道里，接收端被 drop 之后事件直接丢弃
+#[derive(Debug)]
+pub struct ChannelSink {
+    sender: Sender<PoolEvent>,
+}
+
+impl ChannelSink {
+    pub fn new() -> (ChannelSink, Receiver<PoolEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        (ChannelSink { sender }, receiver)
+    }
+}
+
+impl EventSink for ChannelSink {
+    fn event(&self, event: &PoolEvent) {
+        let _ = self.sender.send(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ThreadPool;
+    use std::time::Instant;
+
+    fn pool_with(sink: impl EventSink + 'static, threads: usize) -> ThreadPool {
+        ThreadPool::builder()
+            .num_threads(threads)
+            .event_sink(sink)
+            .panic_handler(|_| {})
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn memory_sink_records_job_lifecycle() {
+        let sink = Arc::new(MemorySink::new());
+        let pool = pool_with(Arc::clone(&sink), 1);
+        assert_eq!(pool.submit(|| 1).join(), Ok(1));
+        pool.execute(|| panic!("boom")).unwrap();
+        drop(pool);
+
+        let events = sink.take();
+        assert_eq!(events[0], PoolEvent::WorkerStarted { worker_id: 0 });
+        assert_eq!(
+            events[1],
+            PoolEvent::JobStarted {
+                worker_id: 0,
+                job_id: 0
+            }
+        );
+        let finished: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                PoolEvent::JobFinished {
+                    job_id, panicked, ..
+                } => Some((*job_id, *panicked)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(finished, vec![(0, false), (1, true)]);
+        assert!(events.contains(&PoolEvent::JoiningWorker { worker_id: 0 }));
+        assert!(events.contains(&PoolEvent::WorkerShutdown { worker_id: 0 }));
+        assert!(sink.events().is_empty());
+    }
+
+    #[test]
+    fn channel_sink_streams_events() {
+        let (sink, events) = ChannelSink::new();
+        let pool = pool_with(sink, 2);
+        pool.execute(|| {}).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let event = events.recv_timeout(deadline - Instant::now()).unwrap();
+            if let PoolEvent::JobFinished { job_id: 0, .. } = event {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn display_matches_old_log_lines() {
+        let lines = [
+            (
+                PoolEvent::JobStarted {
+                    worker_id: 3,
+                    job_id: 9,
+                },
+                "Worker 3 got a job; executing.",
+            ),
+            (
+                PoolEvent::WorkerRetired {
+                    worker_id: 1,
+                    idle: true,
+                },
+                "Worker 1 has been idle; retiring.",
+            ),
+            (
+                PoolEvent::WorkerShutdown { worker_id: 0 },
+                "Worker 0 disconnected; shutting down.",
+            ),
+            (
+                PoolEvent::JoiningWorker { worker_id: 2 },
+                "Shutting down worker 2",
+            ),
+        ];
+        for (event, line) in lines {
+            assert_eq!(event.to_string(), line);
+        }
+    }
+}
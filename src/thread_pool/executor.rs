@@ -0,0 +1,505 @@
+use std::future::Future;
+use std::mem::ManuallyDrop;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, Weak};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Wake, Waker};
+use std::time::{Duration, Instant};
+
+use super::job_handle::{self, Completer, JobHandle};
+use super::{JobError, Priority, ScheduleHandle, Shared};
+
+/// spawn_future 返回的句柄，和 submit 返回的是同一种句柄：可以阻塞 join，也可以 await
+pub type JoinHandle<T> = JobHandle<T>;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// 任务的状态：排队中的 poll 任务最多只有一个，poll 期间被唤醒就在结束后重新排队
+const IDLE: u8 = 0;
+const SCHEDULED: u8 = 1;
+const RUNNING: u8 = 2;
+const NOTIFIED: u8 = 3;
+const DONE: u8 = 4;
+
+/// 线程池里的一个 future，每次被唤醒就作为一个 Job 重新提交，由 Worker poll 一次
+struct Task {
+    future: Mutex<Option<BoxFuture>>,
+    state: AtomicU8,
+    // waker 可能被 future 外面的东西一直拿着，不能让它拖住线程池
+    shared: Weak<Shared>,
+}
+
+impl Task {
+    fn lock(&self) -> MutexGuard<'_, Option<BoxFuture>> {
+        self.future.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wake(self: Arc<Self>) {
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            let next = match state {
+                IDLE => SCHEDULED,
+                RUNNING => NOTIFIED,
+                // 已经在排队、已经通知过或者已经结束
+                _ => return,
+            };
+            match self
+                .state
+                .compare_exchange(state, next, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) if next == SCHEDULED => return self.requeue(),
+                Ok(_) => return,
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    /// 被唤醒后重新排队；线程池已经不在了就直接丢弃 future，句柄得到 Cancelled
+    fn requeue(self: Arc<Self>) {
+        let Some(shared) = self.shared.upgrade() else {
+            self.state.store(DONE, Ordering::SeqCst);
+            drop(self.lock().take());
+            return;
+        };
+        let mut job = poll_job(&shared, self);
+        // 第一次提交时已经被接受过了，之后的 poll 不受有界队列限制，也不能被丢弃
+        job.must_run = true;
+        shared.push_unbounded(job, Priority::Normal);
+    }
+
+    fn poll(self: Arc<Self>) {
+        self.state.store(RUNNING, Ordering::SeqCst);
+        let waker = waker(Arc::clone(&self));
+        let mut cx = Context::from_waker(&waker);
+        let mut slot = self.lock();
+        let Some(future) = slot.as_mut() else {
+            return;
+        };
+        match panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(&mut cx))) {
+            Ok(Poll::Ready(())) => {
+                *slot = None;
+                self.state.store(DONE, Ordering::SeqCst);
+            }
+            Ok(Poll::Pending) => {
+                drop(slot);
+                if self
+                    .state
+                    .compare_exchange(RUNNING, IDLE, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_err()
+                {
+                    // poll 期间被唤醒过
+                    self.state.store(SCHEDULED, Ordering::SeqCst);
+                    self.requeue();
+                }
+            }
+            Err(payload) => {
+                *slot = None;
+                self.state.store(DONE, Ordering::SeqCst);
+                drop(slot);
+                // 继续抛出，让 Worker 通知 panic 处理函数
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+fn poll_job(shared: &Shared, task: Arc<Task>) -> super::Job {
+    shared.new_job(Box::new(move || task.poll()))
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone_waker, wake, wake_by_ref, drop_waker);
+
+// 这几个函数的 ptr 都来自 waker 里的 Arc::into_raw，每个 Waker 持有一个强引用
+unsafe fn clone_waker(ptr: *const ()) -> RawWaker {
+    // SAFETY: ptr 指向的 Task 至少被当前的 Waker 持有
+    unsafe { Arc::increment_strong_count(ptr.cast::<Task>()) };
+    RawWaker::new(ptr, &VTABLE)
+}
+
+unsafe fn wake(ptr: *const ()) {
+    // SAFETY: wake 消耗掉 Waker，连同它持有的强引用
+    let task = unsafe { Arc::from_raw(ptr.cast::<Task>()) };
+    task.wake();
+}
+
+unsafe fn wake_by_ref(ptr: *const ()) {
+    // SAFETY: 只是借用，ManuallyDrop 保证不会减少 Waker 持有的引用计数
+    let task = ManuallyDrop::new(unsafe { Arc::from_raw(ptr.cast::<Task>()) });
+    Arc::clone(&task).wake();
+}
+
+unsafe fn drop_waker(ptr: *const ()) {
+    // SAFETY: 释放 Waker 持有的强引用
+    drop(unsafe { Arc::from_raw(ptr.cast::<Task>()) });
+}
+
+fn waker(task: Arc<Task>) -> Waker {
+    let raw = RawWaker::new(Arc::into_raw(task).cast(), &VTABLE);
+    // SAFETY: VTABLE 里的函数按 Arc<Task> 的引用计数约定实现
+    unsafe { Waker::from_raw(raw) }
+}
+
+/// 把 future 的结果写进句柄；panic 时和 submit 一样先交给句柄，再继续抛出
+struct Spawned<F: Future> {
+    future: Pin<Box<F>>,
+    completer: Option<Completer<F::Output>>,
+}
+
+impl<F: Future> Future for Spawned<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.future.as_mut().poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(value)) => {
+                self.completer.take().unwrap().complete(Ok(value));
+                Poll::Ready(())
+            }
+            Err(payload) => {
+                let message = job_handle::panic_message(payload.as_ref());
+                let completer = self.completer.take().unwrap();
+                completer.complete(Err(JobError::Panicked(message.clone())));
+                panic::resume_unwind(Box::new(message));
+            }
+        }
+    }
+}
+
+/// block_on 的 waker：唤醒调用 block_on 的线程
+#[derive(Default)]
+struct Signal {
+    woken: Mutex<bool>,
+    ready: Condvar,
+}
+
+impl Signal {
+    /// 等到被唤醒或者超时，返回是否被唤醒过
+    fn wait(&self, timeout: Option<Duration>) -> bool {
+        let mut woken = self.woken.lock().unwrap_or_else(PoisonError::into_inner);
+        if !*woken {
+            woken = match timeout {
+                Some(timeout) => {
+                    self.ready
+                        .wait_timeout(woken, timeout)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .ready
+                    .wait_while(woken, |woken| !*woken)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+        std::mem::replace(&mut *woken, false)
+    }
+}
+
+impl Wake for Signal {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        *self.woken.lock().unwrap_or_else(PoisonError::into_inner) = true;
+        self.ready.notify_one();
+    }
+}
+
+/// ThreadPool::sleep 返回的 future，由线程池的定时器唤醒
+pub struct Sleep {
+    shared: Arc<Shared>,
+    deadline: Instant,
+    // 已经登记的定时器和登记时用的 waker
+    registered: Option<(ScheduleHandle, Waker)>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let stale = self
+            .registered
+            .as_ref()
+            .is_none_or(|(_, waker)| !waker.will_wake(cx.waker()));
+        if stale {
+            // 换了 waker（比如 future 被移到了别的任务里），旧的定时器作废
+            if let Some((handle, _)) = self.registered.take() {
+                handle.cancel();
+            }
+            match self.shared.schedule_wake(self.deadline, cx.waker().clone()) {
+                Ok(handle) => self.registered = Some((handle, cx.waker().clone())),
+                // 定时器线程启动不了，只能马上再 poll 一次，下一次 poll 会重试启动
+                Err(_) => cx.waker().wake_by_ref(),
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((handle, _)) = self.registered.take() {
+            handle.cancel();
+        }
+    }
+}
+
+impl Shared {
+    pub(crate) fn spawn_future<F>(self: &Arc<Self>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (handle, completer) = self.handle_pair();
+        let spawned = Spawned {
+            future: Box::pin(future),
+            completer: Some(completer),
+        };
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(Box::pin(spawned))),
+            state: AtomicU8::new(SCHEDULED),
+            shared: Arc::downgrade(self),
+        });
+        // 被拒绝时 future 随任务一起被 drop，句柄得到 Cancelled
+        let _ = self.push(poll_job(self, task), Priority::Normal);
+        handle
+    }
+
+    pub(crate) fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let signal = Arc::new(Signal::default());
+        let waker = Waker::from(Arc::clone(&signal));
+        let mut cx = Context::from_waker(&waker);
+        let worker = self.scheduler.current_worker();
+        loop {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                return value;
+            }
+            match worker {
+                // 在 Worker 上等待时一边等一边执行排队的任务，否则 future 等的任务可能没有 Worker 来执行
+                Some(id) => loop {
+                    match self.scheduler.find_job(id) {
+                        Some(job) => self.run_job(id, job),
+                        None if signal.wait(Some(Duration::from_millis(1))) => break,
+                        None => {}
+                    }
+                },
+                // 确定性线程池在这里一个一个执行排队的任务，直到 future 被唤醒
+                None if self.deterministic.is_some() => loop {
+                    if self.run_next_deterministic() {
+                        if signal.wait(Some(Duration::ZERO)) {
+                            break;
+                        }
+                    } else if self
+                        .deterministic
+                        .as_ref()
+                        .is_some_and(|deterministic| deterministic.wake_on_push(&waker))
+                    {
+                        // 队列空了：等 future 被唤醒，或者定时器（比如 sleep 到期）把任务排进队列
+                        signal.wait(None);
+                        break;
+                    }
+                },
+                None => {
+                    signal.wait(None);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn sleep(self: &Arc<Self>, duration: Duration) -> Sleep {
+        Sleep {
+            shared: Arc::clone(self),
+            deadline: Instant::now() + duration,
+            registered: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thread_pool::OverflowPolicy;
+    use crate::ThreadPool;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// 第一次 poll 返回 Pending 并把 waker 交出去，之后由外部线程唤醒
+    struct Gate {
+        opened: Arc<Mutex<(bool, Option<Waker>)>>,
+    }
+
+    impl Future for Gate {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut gate = self.opened.lock().unwrap();
+            if gate.0 {
+                Poll::Ready(())
+            } else {
+                gate.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn open(gate: &Mutex<(bool, Option<Waker>)>) {
+        let waker = {
+            let mut gate = gate.lock().unwrap();
+            gate.0 = true;
+            gate.1.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    #[test]
+    fn spawned_future_returns_value() {
+        let pool = ThreadPool::new(2);
+        let handle = pool.spawn_future(async { 6 * 7 });
+        assert_eq!(handle.join(), Ok(42));
+    }
+
+    #[test]
+    fn woken_future_is_polled_again() {
+        let pool = ThreadPool::new(2);
+        let opened = Arc::new(Mutex::new((false, None)));
+        let gate = Gate {
+            opened: Arc::clone(&opened),
+        };
+        let mut handle = pool.spawn_future(async move {
+            gate.await;
+            "opened"
+        });
+        assert_eq!(handle.join_timeout(Duration::from_millis(20)), None);
+        // 从一个不属于线程池的线程唤醒
+        thread::spawn(move || open(&opened)).join().unwrap();
+        assert_eq!(
+            handle.join_timeout(Duration::from_secs(5)),
+            Some(Ok("opened"))
+        );
+    }
+
+    #[test]
+    fn many_wakes_poll_at_most_once_at_a_time() {
+        let pool = ThreadPool::new(4);
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&polls);
+        let mut rounds = 0;
+        let handle = pool.spawn_future(std::future::poll_fn(move |cx| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            rounds += 1;
+            if rounds == 100 {
+                return Poll::Ready(rounds);
+            }
+            // 重复唤醒只会让任务多排一次队
+            for _ in 0..3 {
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }));
+        assert_eq!(handle.join(), Ok(100));
+        assert!(polls.load(Ordering::SeqCst) <= 200);
+    }
+
+    #[test]
+    fn panicking_future_reports_to_handle_and_handler() {
+        let (panic_tx, panic_rx) = mpsc::channel();
+        let pool = ThreadPool::with_panic_handler(1, move |panic| panic_tx.send(panic).unwrap());
+        let handle = pool.spawn_future(async {
+            if true {
+                panic!("async boom");
+            }
+        });
+        assert_eq!(
+            handle.join(),
+            Err(JobError::Panicked(String::from("async boom")))
+        );
+        assert_eq!(panic_rx.recv().unwrap().message, "async boom");
+        assert_eq!(pool.spawn_future(async { 1 }).join(), Ok(1));
+    }
+
+    #[test]
+    fn rejected_future_is_cancelled() {
+        let pool = ThreadPool::builder()
+            .num_threads(1)
+            .bounded(1, OverflowPolicy::Reject)
+            .build()
+            .unwrap();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        pool.execute(|| {}).unwrap();
+        assert_eq!(
+            pool.spawn_future(async { 1 }).join(),
+            Err(JobError::Cancelled)
+        );
+        release_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn block_on_awaits_spawned_futures_and_sleep() {
+        let pool = ThreadPool::new(2);
+        let start = Instant::now();
+        let total = pool.block_on(async {
+            let a = pool.spawn_future(async { 1 });
+            let b = pool.spawn_future({
+                let sleep = pool.sleep(Duration::from_millis(30));
+                async move {
+                    sleep.await;
+                    2
+                }
+            });
+            a.await.unwrap() + b.await.unwrap()
+        });
+        assert_eq!(total, 3);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn block_on_inside_a_worker_does_not_deadlock() {
+        let pool = Arc::new(ThreadPool::new(1));
+        let inner = Arc::clone(&pool);
+        let handle = pool.submit(move || inner.block_on(inner.spawn_future(async { 5 })));
+        assert_eq!(handle.join(), Ok(Ok(5)));
+    }
+
+    #[test]
+    fn dropped_sleep_cancels_its_timer() {
+        let pool = ThreadPool::new(1);
+        let opened = Arc::new(Mutex::new((false, None)));
+        let gate = Gate {
+            opened: Arc::clone(&opened),
+        };
+        let handle = pool.spawn_future({
+            let sleep = pool.sleep(Duration::from_secs(60));
+            async move {
+                // 先到的一方获胜，另一个被 drop
+                let mut sleep = pin!(sleep);
+                let mut gate = pin!(gate);
+                std::future::poll_fn(|cx| {
+                    if gate.as_mut().poll(cx).is_ready() {
+                        return Poll::Ready("gate");
+                    }
+                    sleep.as_mut().poll(cx).map(|()| "sleep")
+                })
+                .await
+            }
+        });
+        thread::sleep(Duration::from_millis(10));
+        open(&opened);
+        assert_eq!(handle.join(), Ok("gate"));
+        assert!(pool.shared.timer.is_idle());
+    }
+}
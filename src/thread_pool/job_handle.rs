@@ -0,0 +1,262 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// 任务没能正常产出结果的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobError {
+    /// 任务执行时 panic，携带 panic 信息
+    Panicked(String),
+    /// 任务在执行前就被丢弃了
+    Cancelled,
+}
+
+/// panic 的载荷通常是 &str 或 String，其他类型无法打印
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Panicked(message) => write!(f, "job panicked: {message}"),
+            JobError::Cancelled => write!(f, "job was cancelled before it ran"),
+        }
+    }
+}
+
+impl Error for JobError {}
+
+enum State<T> {
+    Pending,
+    Done(Result<T, JobError>),
+    Taken,
+}
+
+struct Packet<T> {
+    state: Mutex<State<T>>,
+    ready: Condvar,
+    // 被 await 时登记的 waker，要在持有 state 锁时写入，避免错过 finish
+    waker: Mutex<Option<Waker>>,
+}
+
+/// 执行确定性线程池里的下一个任务，没有排队的任务时返回 false
+pub(crate) type Drive = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// ThreadPool::submit 返回的句柄，用来取回任务的结果
+///
+/// 结果只能取出一次：try_join 或 join_timeout 返回 Some 之后，再调用 join 会 panic。
+/// 在 async 代码里也可以直接 await 它。
+pub struct JobHandle<T> {
+    packet: Arc<Packet<T>>,
+    // 确定性线程池没有 Worker，join 时要自己执行排队的任务
+    drive: Option<Drive>,
+}
+
+/// 任务一侧持有的写端，被丢弃而没有写入结果时视为任务被取消
+pub(crate) struct Completer<T> {
+    packet: Option<Arc<Packet<T>>>,
+}
+
+pub(crate) fn pair<T>() -> (JobHandle<T>, Completer<T>) {
+    let packet = Arc::new(Packet {
+        state: Mutex::new(State::Pending),
+        ready: Condvar::new(),
+        waker: Mutex::new(None),
+    });
+    let completer = Completer {
+        packet: Some(Arc::clone(&packet)),
+    };
+    (
+        JobHandle {
+            packet,
+            drive: None,
+        },
+        completer,
+    )
+}
+
+impl<T> Completer<T> {
+    pub(crate) fn complete(mut self, result: Result<T, JobError>) {
+        if let Some(packet) = self.packet.take() {
+            packet.finish(result);
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        if let Some(packet) = self.packet.take() {
+            packet.finish(Err(JobError::Cancelled));
+        }
+    }
+}
+
+impl<T> Packet<T> {
+    fn finish(&self, result: Result<T, JobError>) {
+        *self.state.lock().unwrap() = State::Done(result);
+        self.ready.notify_all();
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> JobHandle<T> {
+    pub(crate) fn with_drive(mut self, drive: Drive) -> JobHandle<T> {
+        self.drive = Some(drive);
+        self
+    }
+
+    /// 阻塞直到任务结束
+    ///
+    /// 确定性线程池的句柄在等待期间在当前线程上执行排队的任务。
+    pub fn join(self) -> Result<T, JobError> {
+        self.drive_until(None);
+        let mut state = self.packet.state.lock().unwrap();
+        while let State::Pending = *state {
+            state = self.packet.ready.wait(state).unwrap();
+        }
+        take(&mut state).expect("JobHandle result already taken")
+    }
+
+    /// 不阻塞，任务还没结束时返回 None
+    pub fn try_join(&mut self) -> Option<Result<T, JobError>> {
+        let mut state = self.packet.state.lock().unwrap();
+        take(&mut state)
+    }
+
+    /// 最多等待 timeout，超时返回 None
+    pub fn join_timeout(&mut self, timeout: Duration) -> Option<Result<T, JobError>> {
+        let deadline = Instant::now() + timeout;
+        self.drive_until(Some(deadline));
+        let mut state = self.packet.state.lock().unwrap();
+        while let State::Pending = *state {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            state = self
+                .packet
+                .ready
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+        take(&mut state)
+    }
+
+    /// 任务是否已经结束（无论成功还是失败）
+    pub fn is_finished(&self) -> bool {
+        !matches!(*self.packet.state.lock().unwrap(), State::Pending)
+    }
+
+    /// 一个一个执行确定性线程池里的任务，直到自己的任务结束或者过了 deadline
+    fn drive_until(&self, deadline: Option<Instant>) {
+        let Some(drive) = &self.drive else {
+            return;
+        };
+        while !self.is_finished() && deadline.is_none_or(|deadline| Instant::now() < deadline) {
+            if !drive() {
+                // 队列空了，任务可能在等定时器把它重新排进来
+                let state = self.packet.state.lock().unwrap();
+                if let State::Pending = *state {
+                    drop(
+                        self.packet
+                            .ready
+                            .wait_timeout(state, Duration::from_millis(1))
+                            .unwrap(),
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl<T> Future for JobHandle<T> {
+    type Output = Result<T, JobError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.packet.state.lock().unwrap();
+        if let Some(result) = take(&mut state) {
+            return Poll::Ready(result);
+        }
+        if let State::Taken = *state {
+            panic!("JobHandle result already taken");
+        }
+        *self.packet.waker.lock().unwrap() = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+fn take<T>(state: &mut State<T>) -> Option<Result<T, JobError>> {
+    match std::mem::replace(state, State::Taken) {
+        State::Done(result) => Some(result),
+        other => {
+            *state = other;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ThreadPool;
+    use std::sync::mpsc;
+
+    #[test]
+    fn submit_returns_value() {
+        let pool = ThreadPool::new(2);
+        let handles: Vec<_> = (0..8).map(|i| pool.submit(move || i * i)).collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, vec![0, 1, 4, 9, 16, 25, 36, 49]);
+    }
+
+    #[test]
+    fn panicked_job_is_reported() {
+        let pool = ThreadPool::new(1);
+        let handle = pool.submit(|| -> i32 { panic!("boom") });
+        assert_eq!(handle.join(), Err(JobError::Panicked(String::from("boom"))));
+        // 捕获了 panic，Worker 还能继续工作
+        assert_eq!(pool.submit(|| 1).join(), Ok(1));
+    }
+
+    #[test]
+    fn try_join_and_timeout() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel::<()>();
+        let mut handle = pool.submit(move || {
+            rx.recv().unwrap();
+            "done"
+        });
+        assert_eq!(handle.try_join(), None);
+        assert_eq!(handle.join_timeout(Duration::from_millis(20)), None);
+        assert!(!handle.is_finished());
+
+        tx.send(()).unwrap();
+        assert_eq!(
+            handle.join_timeout(Duration::from_secs(5)),
+            Some(Ok("done"))
+        );
+        assert_eq!(handle.try_join(), None);
+    }
+
+    #[test]
+    fn dropped_completer_cancels() {
+        let (handle, completer) = pair::<()>();
+        drop(completer);
+        assert_eq!(handle.join(), Err(JobError::Cancelled));
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::{ExecuteError, Priority, Shared};
+
+type KeyedJob = Box<dyn FnOnce() + Send + 'static>;
+
+/// 按 key 串行执行的任务队列
+///
+/// map 里有某个 key 就表示线程池里正好有一个它的“排水”任务在排队或者执行。
+/// 排水任务每次只执行一个任务，还有剩下的就把自己重新排到全局队列末尾，所以一个 key 最多占用一个 Worker，
+/// 其他 key 的任务可以插在中间执行，不会被某个 key 的长队列堵住。队列空了就把 key 删掉。
+#[derive(Default)]
+pub(crate) struct KeyedQueues {
+    // key 按哈希值存放：两个 key 哈希冲突时只会被串行执行，顺序仍然正确
+    queues: Mutex<HashMap<u64, VecDeque<KeyedJob>>>,
+}
+
+impl KeyedQueues {
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, VecDeque<KeyedJob>>> {
+        self.queues.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 还有任务的 key 的数量
+    #[cfg(test)]
+    pub(crate) fn active_keys(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// 丢弃所有还在排队的按 key 任务，返回丢弃的数量；排水任务看到队列空了会自己清理 key
+    pub(crate) fn clear(&self) -> usize {
+        let removed: Vec<KeyedJob> = self
+            .lock()
+            .values_mut()
+            .flat_map(|queue| queue.drain(..))
+            .collect();
+        removed.len()
+    }
+}
+
+pub(crate) fn hash_key<K: Hash>(key: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Shared {
+    pub(crate) fn execute_keyed(
+        self: &Arc<Self>,
+        key: u64,
+        f: KeyedJob,
+    ) -> Result<(), ExecuteError> {
+        match self.keyed.lock().entry(key) {
+            // 已经有排水任务，排在后面就行
+            Entry::Occupied(mut queue) => {
+                queue.get_mut().push_back(f);
+                return Ok(());
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(VecDeque::from([f]));
+            }
+        }
+
+        let mut job = self.drainer(key);
+        // 排水任务要把 key 清理掉，不能被丢弃或取消
+        job.must_run = true;
+        let result = self.push(job, Priority::Normal);
+        if result.is_err() {
+            let mut queues = self.keyed.lock();
+            let queue = queues.get_mut(&key).unwrap();
+            // 排水任务被拒绝了，自己的任务还在队头
+            drop(queue.pop_front());
+            if queue.is_empty() {
+                queues.remove(&key);
+            } else {
+                // 别人以为排水任务已经存在，把任务接在了后面，只能越过容量限制补上
+                drop(queues);
+                self.resubmit_drainer(key);
+            }
+        }
+        result
+    }
+
+    fn drainer(self: &Arc<Self>, key: u64) -> super::Job {
+        let shared = Arc::clone(self);
+        self.new_job(Box::new(move || shared.drain_key(key)))
+    }
+
+    fn resubmit_drainer(self: &Arc<Self>, key: u64) {
+        let mut job = self.drainer(key);
+        job.must_run = true;
+        // 这些任务已经被接受过了，后续的排水任务不受有界队列限制。
+        // 不能进 Worker 自己的队列：那是后进先出的，同一个 key 会一直占着这个 Worker
+        self.requeue(job, Priority::Normal);
+    }
+
+    /// 执行 key 的下一个任务，还有剩下的就重新提交自己
+    fn drain_key(self: &Arc<Self>, key: u64) {
+        let job = {
+            let mut queues = self.keyed.lock();
+            match queues.get_mut(&key).and_then(VecDeque::pop_front) {
+                Some(job) => job,
+                // 被 cancel_all 清空了
+                None => {
+                    queues.remove(&key);
+                    return;
+                }
+            }
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(job));
+
+        let more = {
+            let mut queues = self.keyed.lock();
+            let empty = queues.get(&key).is_none_or(VecDeque::is_empty);
+            if empty {
+                queues.remove(&key);
+            }
+            !empty
+        };
+        if more {
+            self.resubmit_drainer(key);
+        }
+        // 后续任务安排好之后再继续抛出，让 Worker 把 panic 交给处理函数
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_pool::{ExecuteError, OverflowPolicy};
+    use crate::ThreadPool;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn same_key_runs_in_submission_order() {
+        let pool = ThreadPool::new(4);
+        let seen: Arc<Mutex<HashMap<u32, Vec<u32>>>> = Arc::default();
+        for i in 0..400 {
+            let key = i % 4;
+            let seen = Arc::clone(&seen);
+            pool.execute_keyed(key, move || {
+                if i % 7 == 0 {
+                    thread::sleep(Duration::from_micros(200));
+                }
+                seen.lock().unwrap().entry(key).or_default().push(i);
+            })
+            .unwrap();
+        }
+        wait_until(|| pool.shared.keyed.active_keys() == 0);
+        for (key, order) in seen.lock().unwrap().iter() {
+            let expected: Vec<u32> = (0..400).filter(|i| i % 4 == *key).collect();
+            assert_eq!(order, &expected);
+        }
+    }
+
+    #[test]
+    fn same_key_never_overlaps() {
+        let pool = ThreadPool::new(4);
+        let running = Arc::new(AtomicUsize::new(0));
+        let overlaps = Arc::new(AtomicUsize::new(0));
+        for _ in 0..50 {
+            let (running, overlaps) = (Arc::clone(&running), Arc::clone(&overlaps));
+            pool.execute_keyed("user-1", move || {
+                if running.fetch_add(1, Ordering::SeqCst) > 0 {
+                    overlaps.fetch_add(1, Ordering::SeqCst);
+                }
+                thread::sleep(Duration::from_micros(100));
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+        wait_until(|| pool.shared.keyed.active_keys() == 0);
+        assert_eq!(overlaps.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn slow_key_does_not_block_other_keys() {
+        let pool = ThreadPool::new(2);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute_keyed("slow", move || {
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        for _ in 0..5 {
+            pool.execute_keyed("slow", || {}).unwrap();
+        }
+        let (tx, rx) = mpsc::channel();
+        for i in 0..5 {
+            let tx = tx.clone();
+            pool.execute_keyed("fast", move || tx.send(i).unwrap())
+                .unwrap();
+        }
+        // slow 的队列还卡着，fast 的任务照样全部完成
+        let order: Vec<_> = (0..5)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(order, vec![0, 1, 2, 3, 4]);
+        release_tx.send(()).unwrap();
+        wait_until(|| pool.shared.keyed.active_keys() == 0);
+    }
+
+    #[test]
+    fn panicking_job_does_not_stall_its_key() {
+        let (panic_tx, panic_rx) = mpsc::channel();
+        let pool = ThreadPool::with_panic_handler(1, move |panic| panic_tx.send(panic).unwrap());
+        let (tx, rx) = mpsc::channel();
+        pool.execute_keyed(1, || panic!("first")).unwrap();
+        pool.execute_keyed(1, move || tx.send("second").unwrap())
+            .unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("second"));
+        assert_eq!(panic_rx.recv().unwrap().message, "first");
+    }
+
+    #[test]
+    fn rejected_key_is_cleaned_up() {
+        let pool = ThreadPool::builder()
+            .num_threads(1)
+            .bounded(1, OverflowPolicy::Reject)
+            .build()
+            .unwrap();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        pool.execute(|| {}).unwrap();
+
+        assert_eq!(pool.execute_keyed(7, || {}), Err(ExecuteError::QueueFull));
+        assert_eq!(pool.shared.keyed.active_keys(), 0);
+        release_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn keys_take_turns_on_a_single_worker() {
+        let pool = ThreadPool::new(1);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        for key in ["a", "b"] {
+            for i in 0..4 {
+                let tx = tx.clone();
+                pool.execute_keyed(key, move || tx.send(format!("{key}{i}")).unwrap())
+                    .unwrap();
+            }
+        }
+        drop(tx);
+        release_tx.send(()).unwrap();
+        let order: Vec<_> = rx.iter().collect();
+        assert_eq!(order, ["a0", "b0", "a1", "b1", "a2", "b2", "a3", "b3"]);
+    }
+}
@@ -0,0 +1,739 @@
+mod broadcast;
+mod builder;
+mod cancel;
+mod deterministic;
+mod events;
+mod executor;
+mod job_handle;
+mod keyed;
+mod par_iter;
+mod priority;
+mod scheduler;
+mod scope;
+mod shutdown;
+mod sizing;
+mod stats;
+mod task_graph;
+mod timer;
+mod watchdog;
+mod worker_state;
+
+use std::future::Future;
+use std::hash::Hash;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use self::builder::Config;
+pub use self::builder::{PoolCreationError, ThreadPoolBuilder};
+pub use self::cancel::CancellationToken;
+use self::deterministic::Deterministic;
+pub use self::events::{ChannelSink, EventSink, MemorySink, NullSink, PoolEvent, StdoutSink};
+pub use self::executor::{JoinHandle, Sleep};
+pub use self::job_handle::{JobError, JobHandle};
+use self::keyed::KeyedQueues;
+pub use self::par_iter::ParallelSlice;
+pub use self::priority::Priority;
+pub use self::scheduler::{ExecuteError, OverflowPolicy};
+use self::scheduler::{Next, Overflow, Scheduler};
+pub use self::scope::Scope;
+pub use self::shutdown::{ShutdownMode, ShutdownReport};
+use self::sizing::Sizing;
+use self::stats::Metrics;
+pub use self::stats::{HistogramSnapshot, PoolStats, StatsSink, WorkerStats};
+pub use self::task_graph::{GraphError, GraphReport, NodeId, NodeOutcome, TaskGraph, TraceEntry};
+pub use self::timer::ScheduleHandle;
+use self::timer::Timer;
+use self::watchdog::Watchdog;
+use self::worker_state::WorkerState;
+
+/// 任务 panic 时交给 panic 处理函数的信息
+#[derive(Debug, Clone)]
+pub struct JobPanic {
+    pub worker_id: usize,
+    pub job_id: u64,
+    pub message: String,
+}
+
+type PanicHandler = Arc<dyn Fn(JobPanic) + Send + Sync + 'static>;
+
+struct Worker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(id: usize, shared: Arc<Shared>) -> io::Result<Worker> {
+        let mut builder = thread::Builder::new();
+        if let Some(prefix) = &shared.config.thread_name {
+            builder = builder.name(format!("{prefix}-{id}"));
+        }
+        if let Some(size) = shared.config.stack_size {
+            builder = builder.stack_size(size);
+        }
+
+        let thread = builder.spawn(move || {
+            // 哨兵：线程退出时被 drop，负责调用 on_thread_stop；因为 panic 退出时还要补上一个新的 Worker
+            let _sentinel = Sentinel {
+                id,
+                shared: &shared,
+            };
+            shared.scheduler.enter(id);
+            if let Some(hook) = &shared.config.on_thread_start {
+                hook(id);
+            }
+            shared.emit(PoolEvent::WorkerStarted { worker_id: id });
+            let counters = shared.metrics.worker(id);
+            loop {
+                let waiting = Instant::now();
+                let next = shared.scheduler.next_job(id, shared.sizing.keep_alive);
+                counters.add_idle(waiting.elapsed());
+                match next {
+                    Next::Job(job) => {
+                        // 取走一个任务后队列里还有积压，说明需要更多 Worker
+                        shared.maybe_grow();
+                        let started = Instant::now();
+                        shared.run_job(id, job);
+                        counters.add_busy(started.elapsed());
+                        if shared.should_retire(id, false) {
+                            shared.emit(PoolEvent::WorkerRetired {
+                                worker_id: id,
+                                idle: false,
+                            });
+                            break;
+                        }
+                    }
+                    Next::Idle { timed_out } => {
+                        if shared.should_retire(id, timed_out) {
+                            shared.emit(PoolEvent::WorkerRetired {
+                                worker_id: id,
+                                idle: true,
+                            });
+                            break;
+                        }
+                    }
+                    Next::Shutdown => {
+                        shared.emit(PoolEvent::WorkerShutdown { worker_id: id });
+                        break;
+                    }
+                }
+            }
+        })?;
+
+        Ok(Worker {
+            id,
+            thread: Some(thread),
+        })
+    }
+
+    fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(|thread| thread.is_finished())
+    }
+}
+
+struct Sentinel<'a> {
+    id: usize,
+    shared: &'a Arc<Shared>,
+}
+
+impl Drop for Sentinel<'_> {
+    fn drop(&mut self) {
+        if let Some(hook) = &self.shared.config.on_thread_stop {
+            // 展开过程中再次 panic 会直接 abort，所以钩子的 panic 在这里吞掉
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(self.id)));
+        }
+        if thread::panicking() {
+            self.shared
+                .emit(PoolEvent::WorkerDied { worker_id: self.id });
+            match Worker::new(self.id, Arc::clone(self.shared)) {
+                Ok(worker) => self
+                    .shared
+                    .workers
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(worker),
+                Err(err) => self.shared.emit(PoolEvent::SpawnFailed {
+                    worker_id: Some(self.id),
+                    error: err.to_string(),
+                }),
+            }
+        }
+    }
+}
+
+/// 所有 Worker 共享的状态
+struct Shared {
+    scheduler: Scheduler,
+    sizing: Sizing,
+    timer: Arc<Timer>,
+    watchdog: Watchdog,
+    // ThreadPool::deterministic 创建的线程池没有 Worker，任务都排在这里
+    deterministic: Option<Deterministic>,
+    keyed: KeyedQueues,
+    next_job_id: AtomicU64,
+    metrics: Metrics,
+    // 包括被替换掉和已经退休的 Worker，Drop 时要全部 join
+    workers: Mutex<Vec<Worker>>,
+    panic_handler: PanicHandler,
+    event_sink: Arc<dyn EventSink>,
+    config: Config,
+}
+
+impl Shared {
+    /// 把事件交给 sink；sink 自己 panic 时吞掉，Worker 的 Sentinel 在展开过程中也会发事件
+    fn emit(&self, event: PoolEvent) {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| self.event_sink.event(&event)));
+    }
+
+    /// 在 Worker id 上执行任务，panic 交给 panic 处理函数；已经取消的任务直接丢弃
+    fn run_job(&self, worker_id: usize, job: Job) {
+        self.run_job_with(worker_id, job, true);
+    }
+
+    /// 按 CallerRuns 在提交任务的线程上执行，和 Worker 一样捕获 panic、记录统计和事件
+    ///
+    /// 外部线程没有 Worker id，记在 0 号上，但不占用 0 号 Worker 在看门狗里的记录。
+    fn run_on_caller(&self, job: Job) {
+        match self.scheduler.current_worker() {
+            Some(id) => self.run_job(id, job),
+            None => self.run_job_with(0, job, false),
+        }
+    }
+
+    fn run_job_with(&self, worker_id: usize, job: Job, track: bool) {
+        if job.is_cancelled() {
+            self.emit(PoolEvent::JobSkipped {
+                worker_id,
+                job_id: job.id,
+            });
+            return;
+        }
+        self.emit(PoolEvent::JobStarted {
+            worker_id,
+            job_id: job.id,
+        });
+        let tracking = if track {
+            self.watchdog.track(worker_id, &job)
+        } else {
+            None
+        };
+        let started = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(job.run));
+        let elapsed = started.elapsed();
+        drop(tracking);
+        self.metrics.record_job(started - job.queued_at, elapsed);
+        self.metrics.completed.fetch_add(1, Ordering::SeqCst);
+        self.emit(PoolEvent::JobFinished {
+            worker_id,
+            job_id: job.id,
+            elapsed,
+            panicked: result.is_err(),
+        });
+        if let Err(payload) = result {
+            self.metrics.panicked.fetch_add(1, Ordering::SeqCst);
+            (self.panic_handler)(JobPanic {
+                worker_id,
+                job_id: job.id,
+                message: job_handle::panic_message(payload.as_ref()),
+            });
+        }
+    }
+
+    fn new_job(&self, run: Box<dyn FnOnce() + Send + 'static>) -> Job {
+        Job {
+            id: self.next_job_id.fetch_add(1, Ordering::Relaxed),
+            run,
+            token: None,
+            must_run: false,
+            label: None,
+            queued_at: Instant::now(),
+        }
+    }
+
+    fn execute(
+        self: &Arc<Self>,
+        priority: Priority,
+        run: Box<dyn FnOnce() + Send + 'static>,
+    ) -> Result<(), ExecuteError> {
+        self.push(self.new_job(run), priority)
+    }
+
+    fn push(self: &Arc<Self>, job: Job, priority: Priority) -> Result<(), ExecuteError> {
+        if let Some(deterministic) = &self.deterministic {
+            self.metrics.submitted.fetch_add(1, Ordering::SeqCst);
+            deterministic.push(job, priority);
+            return Ok(());
+        }
+        // 在 Worker 里提交的 Normal 任务进入该 Worker 自己的队列，其余的进入全局队列
+        match self.scheduler.push(job, priority) {
+            Ok(()) => {
+                self.metrics.submitted.fetch_add(1, Ordering::SeqCst);
+                self.maybe_grow();
+                Ok(())
+            }
+            Err(Overflow::Rejected) => {
+                self.metrics.rejected.fetch_add(1, Ordering::SeqCst);
+                Err(ExecuteError::QueueFull)
+            }
+            Err(Overflow::RunOnCaller(job)) => {
+                self.metrics.submitted.fetch_add(1, Ordering::SeqCst);
+                self.run_on_caller(job);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// 已经被接受过的任务再次排队，不受有界队列限制
+    fn push_unbounded(&self, job: Job, priority: Priority) {
+        match &self.deterministic {
+            Some(deterministic) => deterministic.push(job, priority),
+            None => self.scheduler.push_unbounded(job, priority),
+        }
+    }
+
+    /// 和 push_unbounded 一样，但总是排到全局队列末尾，先让别的任务执行
+    fn requeue(&self, job: Job, priority: Priority) {
+        match &self.deterministic {
+            Some(deterministic) => deterministic.push(job, priority),
+            None => self.scheduler.requeue(job, priority),
+        }
+    }
+
+    /// 取出所有可以丢弃的排队任务
+    fn remove_cancellable(&self) -> Vec<Job> {
+        match &self.deterministic {
+            Some(deterministic) => deterministic.remove_cancellable(),
+            None => self.scheduler.remove_cancellable(),
+        }
+    }
+
+    fn queue_depth(&self) -> usize {
+        self.deterministic
+            .as_ref()
+            .map_or_else(|| self.scheduler.len(), Deterministic::len)
+    }
+}
+
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+}
+
+struct Job {
+    id: u64,
+    run: Box<dyn FnOnce() + Send + 'static>,
+    // 被取消后不再执行
+    token: Option<CancellationToken>,
+    // scope 里的任务被借用的数据还在等它，cancel_all 不能丢弃
+    must_run: bool,
+    // 看门狗报告卡住的任务时带上
+    label: Option<Arc<str>>,
+    queued_at: Instant,
+}
+
+impl Job {
+    fn is_cancelled(&self) -> bool {
+        self.token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+}
+
+/// 把有返回值的任务包装成 Job 的闭包，结果写进返回的 JobHandle
+fn packaged<F, T>(shared: &Arc<Shared>, f: F) -> (JobHandle<T>, Box<dyn FnOnce() + Send + 'static>)
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let (handle, completer) = shared.handle_pair();
+    let run = Box::new(move || match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => completer.complete(Ok(value)),
+        Err(payload) => {
+            // 先把 panic 作为 JobError 交给调用方，再继续抛出，让 Worker 通知 panic 处理函数。
+            let message = job_handle::panic_message(payload.as_ref());
+            completer.complete(Err(JobError::Panicked(message.clone())));
+            panic::resume_unwind(Box::new(message));
+        }
+    });
+    (handle, run)
+}
+
+impl ThreadPool {
+    /// 创建 size 个 Worker 的线程池，size 为 0 或线程创建失败时 panic
+    pub fn new(size: usize) -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .num_threads(size)
+            .build()
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// 指定任务 panic 时的处理函数，默认实现只是打印到标准错误
+    pub fn with_panic_handler<H>(size: usize, handler: H) -> ThreadPool
+    where
+        H: Fn(JobPanic) + Send + Sync + 'static,
+    {
+        ThreadPoolBuilder::new()
+            .num_threads(size)
+            .panic_handler(handler)
+            .build()
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn builder() -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new()
+    }
+
+    /// 用于测试的确定性线程池：没有 Worker 线程，任务在调用 run_until_idle 的线程上按 seed 决定的顺序执行
+    ///
+    /// 同样的 seed 和提交顺序总是得到同样的执行顺序，把日志里的 seed 传进来就能重现一次执行。
+    /// 其余 API 不变；scope、block_on 和关闭线程池时也会在当前线程上执行排队的任务。
+    /// 定时任务仍然按真实时间到期，到期后和其他任务一起排队。
+    pub fn deterministic(seed: u64) -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .deterministic(seed)
+            .build()
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// 确定性线程池的种子，普通线程池返回 None
+    pub fn seed(&self) -> Option<u64> {
+        self.shared
+            .deterministic
+            .as_ref()
+            .map(|deterministic| deterministic.seed)
+    }
+
+    /// 执行到没有排队的任务为止，返回这期间执行完的任务数
+    ///
+    /// 确定性线程池在当前线程上执行；普通线程池等到队列为空、所有 Worker 都空闲。
+    pub fn run_until_idle(&self) -> usize {
+        self.shared.run_until_idle()
+    }
+
+    /// 当前在岗的 Worker 数
+    pub fn num_workers(&self) -> usize {
+        self.shared.sizing.live()
+    }
+
+    /// 运行状态的快照：队列长度、任务计数、每个 Worker 的忙闲时间和等待、执行时间的直方图
+    pub fn stats(&self) -> PoolStats {
+        self.shared.stats()
+    }
+
+    /// 把 Worker 数量固定为 n，之后不再自动伸缩
+    ///
+    /// 扩容时立即启动新的 Worker；缩容时多出的 Worker 做完手头的任务后退休。
+    pub fn resize(&self, n: usize) -> Result<(), PoolCreationError> {
+        if n == 0 {
+            return Err(PoolCreationError::ZeroThreads);
+        }
+        self.shared.resize(n).map_err(PoolCreationError::Spawn)
+    }
+
+    //闭包作为参数时可以使用三个不同的 trait：Fn、FnMut 和 FnOnce。
+    /// 无界队列下总是成功；有界队列满了之后的行为由 OverflowPolicy 决定
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute_with_priority(Priority::Normal, f)
+    }
+
+    /// 按优先级提交任务：High 先于 Normal 先于 Low，低优先级的任务等待过久会被提前处理
+    pub fn execute_with_priority<F>(&self, priority: Priority, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared.execute(priority, Box::new(f))
+    }
+
+    /// 带标签提交任务，看门狗报告这个任务卡住时会带上 label
+    pub fn execute_labeled<F>(&self, label: impl Into<String>, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut job = self.shared.new_job(Box::new(f));
+        job.label = Some(Arc::from(label.into()));
+        self.shared.push(job, Priority::Normal)
+    }
+
+    /// 提交一个有返回值的任务，通过返回的 JobHandle 取回结果
+    ///
+    /// 被有界队列拒绝或丢弃的任务，join 时得到 JobError::Cancelled。
+    pub fn submit<F, T>(&self, f: F) -> JobHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (handle, run) = packaged(&self.shared, f);
+        // 被拒绝时任务连同 completer 一起被 drop，结果会是 Cancelled
+        let _ = self.shared.execute(Priority::Normal, run);
+        handle
+    }
+
+    /// 带标签的 submit，标签的用途同 execute_labeled
+    pub fn submit_labeled<F, T>(&self, label: impl Into<String>, f: F) -> JobHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (handle, run) = packaged(&self.shared, f);
+        let mut job = self.shared.new_job(run);
+        job.label = Some(Arc::from(label.into()));
+        let _ = self.shared.push(job, Priority::Normal);
+        handle
+    }
+
+    /// 提交一个可以取消的任务：token 被取消时如果还在排队就不再执行，正在执行的任务可以通过参数检查 token
+    pub fn execute_cancellable<F>(
+        &self,
+        token: &CancellationToken,
+        f: F,
+    ) -> Result<(), ExecuteError>
+    where
+        F: FnOnce(&CancellationToken) + Send + 'static,
+    {
+        let mut job = self.shared.new_job(Box::new({
+            let token = token.clone();
+            move || f(&token)
+        }));
+        job.token = Some(token.clone());
+        self.shared.push(job, Priority::Normal)
+    }
+
+    /// 提交一个使用 Worker 状态的任务，状态由 ThreadPoolBuilder::with_worker_state 设置
+    ///
+    /// 线程池没有设置状态、或者状态不是 S 类型时 panic。
+    pub fn execute_with_state<S, F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        S: 'static,
+        F: FnOnce(&mut S) + Send + 'static,
+    {
+        let state = self.worker_state::<S>();
+        self.execute(move || state.with(f))
+    }
+
+    /// 在每个 Worker 上各执行一次 f，参数是 Worker id，返回按 id 排列的结果
+    ///
+    /// f 可以借用调用方的数据，等所有 Worker 都执行完才返回；正忙的 Worker 会在手头的任务结束后执行。
+    /// f panic 时在这里重新抛出。
+    pub fn broadcast<F, T>(&self, f: F) -> Vec<T>
+    where
+        F: Fn(usize) -> T + Sync,
+        T: Send,
+    {
+        self.shared.broadcast(f)
+    }
+
+    /// 带 Worker 状态的 broadcast，比如收集或清空每个 Worker 的缓冲区
+    pub fn broadcast_with_state<S, F, T>(&self, f: F) -> Vec<T>
+    where
+        S: 'static,
+        F: Fn(usize, &mut S) -> T + Sync,
+        T: Send,
+    {
+        let state = self.worker_state::<S>();
+        self.shared
+            .broadcast(|id| state.with(|state: &mut S| f(id, state)))
+    }
+
+    fn worker_state<S: 'static>(&self) -> Arc<WorkerState> {
+        let state =
+            self.shared.config.worker_state.as_ref().expect(
+                "thread pool has no worker state; use ThreadPoolBuilder::with_worker_state",
+            );
+        state.check::<S>();
+        Arc::clone(state)
+    }
+
+    /// 按 key 串行提交：同一个 key 的任务按提交顺序一个接一个执行，不同 key 的任务并行执行
+    ///
+    /// 一个 key 同一时间最多占用一个 Worker，某个 key 积压很多任务也不会挡住其他 key。
+    /// 有界队列只限制每个 key 的第一个任务，排在已有 key 后面的任务总是被接受。
+    pub fn execute_keyed<K, F>(&self, key: K, f: F) -> Result<(), ExecuteError>
+    where
+        K: Hash,
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared
+            .execute_keyed(keyed::hash_key(&key), Box::new(f))
+    }
+
+    /// 可以取消的 submit，任务在执行之前被取消时 join 得到 JobError::Cancelled
+    pub fn submit_cancellable<F, T>(&self, token: &CancellationToken, f: F) -> JobHandle<T>
+    where
+        F: FnOnce(&CancellationToken) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let token = token.clone();
+        let job_token = token.clone();
+        let (handle, run) = packaged(&self.shared, move || f(&job_token));
+        let mut job = self.shared.new_job(run);
+        job.token = Some(token);
+        let _ = self.shared.push(job, Priority::Normal);
+        handle
+    }
+
+    /// 取消所有还在排队的任务，返回取消的数量
+    ///
+    /// 任务立即被丢弃，它们的 JobHandle 得到 JobError::Cancelled；正在执行的任务和 scope 里的任务不受影响。
+    /// execute_keyed 排队中的任务也会被丢弃。
+    /// 之后再 drop 线程池，就不用等排队的任务一个个执行完。
+    pub fn cancel_all(&self) -> usize {
+        let removed = self.shared.remove_cancellable();
+        removed.len() + self.shared.keyed.clear()
+    }
+
+    /// 关闭线程池，最多等到 deadline
+    ///
+    /// Drain 先做完排队的任务，DiscardPending 直接丢弃它们；两种方式都会等正在执行的任务结束。
+    /// 到了 deadline 还在排队的任务被丢弃，还没退出的 Worker 记录在报告里，不再等待。
+    pub fn shutdown(self, mode: ShutdownMode, deadline: Instant) -> ShutdownReport {
+        // 之后的 drop 看到 Worker 都已经被取走，不会再等待
+        self.shared.shutdown(mode, deadline)
+    }
+
+    /// 创建一个作用域，里面用 Scope::spawn 提交的任务可以借用栈上的数据
+    ///
+    /// 任务在已有的 Worker 上执行，scope 等所有任务结束后才返回；任务 panic 时在这里重新抛出。
+    pub fn scope<'env, F, T>(&self, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        self.shared.scope(f)
+    }
+
+    /// 在线程池上运行一个 future，通过返回的 JoinHandle 取回结果
+    ///
+    /// future 每次被唤醒都会作为一个任务重新排队，由某个 Worker poll 一次，所以 future 里不要阻塞。
+    /// 被有界队列拒绝时 join 得到 JobError::Cancelled，panic 时得到 JobError::Panicked。
+    pub fn spawn_future<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.shared.spawn_future(future)
+    }
+
+    /// 在当前线程上把 future 运行到结束，future 里可以 spawn_future、await 线程池上的任务
+    ///
+    /// 在本线程池的 Worker 上调用时，等待期间会执行排队的任务。
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.shared.block_on(future)
+    }
+
+    /// 等待 duration 的 future，由线程池的定时器唤醒，不占用 Worker
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        self.shared.sleep(duration)
+    }
+
+    /// 等待 delay 之后把任务交给线程池执行
+    ///
+    /// 定时器线程在第一次调用时启动，启动失败时返回错误。到期时有界队列已满的话，
+    /// 任务不会在定时器线程上执行，而是被丢弃并计入 stats().rejected。
+    pub fn schedule_after<F>(&self, delay: Duration, f: F) -> io::Result<ScheduleHandle>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.schedule_at(Instant::now() + delay, f)
+    }
+
+    /// 到 deadline 时把任务交给线程池执行，deadline 已经过去时尽快执行
+    pub fn schedule_at<F>(&self, deadline: Instant, f: F) -> io::Result<ScheduleHandle>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared.schedule_once(deadline, Box::new(f))
+    }
+
+    /// 固定频率执行：第 n 次在 n * period 之后开始，执行耗时不影响后面的时间点
+    ///
+    /// 上一次还没执行完时不会开始下一次，落后的次数在上一次结束后立即补上。
+    /// f panic 之后不再执行。
+    pub fn schedule_every<F>(&self, period: Duration, f: F) -> io::Result<ScheduleHandle>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.shared.schedule_fixed_rate(period, Arc::new(f))
+    }
+
+    /// 固定间隔执行：每次执行结束后再等 delay 才开始下一次
+    pub fn schedule_with_fixed_delay<F>(&self, delay: Duration, f: F) -> io::Result<ScheduleHandle>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.shared.schedule_fixed_delay(delay, Arc::new(f))
+    }
+}
+
+/// drop 时按 ShutdownMode::Drain 关闭：做完排队的任务，最多等 30 秒，超时的 Worker 不再等待
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared
+            .shutdown(ShutdownMode::Drain, Instant::now() + shutdown::DROP_TIMEOUT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn panicking_jobs_do_not_shrink_pool() {
+        let (tx, rx) = mpsc::channel();
+        let pool = ThreadPool::with_panic_handler(2, move |panic| tx.send(panic).unwrap());
+        for i in 0..10 {
+            pool.execute(move || panic!("job {i} failed")).unwrap();
+        }
+        let handles: Vec<_> = (0..10).map(|i| pool.submit(move || i)).collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join(), Ok(i));
+        }
+
+        let mut panics: Vec<_> = (0..10)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        panics.sort_by_key(|panic| panic.job_id);
+        for (i, panic) in panics.iter().enumerate() {
+            assert_eq!(panic.job_id, i as u64);
+            assert_eq!(panic.message, format!("job {i} failed"));
+            assert!(panic.worker_id < 2);
+        }
+    }
+
+    #[test]
+    fn submit_panic_reaches_handler() {
+        let (tx, rx) = mpsc::channel();
+        let pool = ThreadPool::with_panic_handler(1, move |panic| tx.send(panic).unwrap());
+        let handle = pool.submit(|| panic!("boom"));
+        assert_eq!(handle.join(), Err(JobError::Panicked(String::from("boom"))));
+        let panic = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((panic.worker_id, panic.job_id), (0, 0));
+        assert_eq!(panic.message, "boom");
+    }
+
+    #[test]
+    fn dead_worker_is_replaced() {
+        // 处理函数自己 panic 会让 Worker 线程退出，哨兵应当补上新的 Worker
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let pool = ThreadPool::with_panic_handler(1, move |_| {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("handler failed");
+            }
+        });
+        pool.execute(|| panic!("first")).unwrap();
+        assert_eq!(pool.submit(|| 42).join(), Ok(42));
+        drop(pool);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
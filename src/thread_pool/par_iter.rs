@@ -0,0 +1,199 @@
+use super::ThreadPool;
+
+/// 切片上的并行版 map、for_each、filter、reduce，在给定的线程池上执行
+///
+/// Vec 通过自动解引用也能直接使用。结果的顺序和顺序执行时一样。
+pub trait ParallelSlice<T: Sync> {
+    /// 并行地对每个元素调用 f，结果按原来的顺序排列
+    fn par_map<U, F>(&self, pool: &ThreadPool, f: F) -> Vec<U>
+    where
+        U: Send,
+        F: Fn(&T) -> U + Sync;
+
+    /// 并行地对每个元素调用 f，不保证调用顺序
+    fn par_for_each<F>(&self, pool: &ThreadPool, f: F)
+    where
+        F: Fn(&T) + Sync;
+
+    /// 保留 predicate 返回 true 的元素，顺序不变
+    fn par_filter<F>(&self, pool: &ThreadPool, predicate: F) -> Vec<&T>
+    where
+        F: Fn(&T) -> bool + Sync;
+
+    /// 用 f 把所有元素归约成一个值，切片为空时返回 None
+    ///
+    /// 相邻的元素先分组归约再合并，元素的先后顺序不变，所以 f 只需要满足结合律，不需要满足交换律。
+    fn par_reduce<F>(&self, pool: &ThreadPool, f: F) -> Option<T>
+    where
+        T: Clone + Send,
+        F: Fn(T, T) -> T + Sync;
+}
+
+impl<T: Sync> ParallelSlice<T> for [T] {
+    fn par_map<U, F>(&self, pool: &ThreadPool, f: F) -> Vec<U>
+    where
+        U: Send,
+        F: Fn(&T) -> U + Sync,
+    {
+        let chunks = bridge(pool, self, &|chunk| {
+            chunk.iter().map(&f).collect::<Vec<_>>()
+        });
+        chunks.into_iter().flatten().collect()
+    }
+
+    fn par_for_each<F>(&self, pool: &ThreadPool, f: F)
+    where
+        F: Fn(&T) + Sync,
+    {
+        bridge(pool, self, &|chunk| chunk.iter().for_each(&f));
+    }
+
+    fn par_filter<F>(&self, pool: &ThreadPool, predicate: F) -> Vec<&T>
+    where
+        F: Fn(&T) -> bool + Sync,
+    {
+        let chunks = bridge(pool, self, &|chunk| {
+            chunk
+                .iter()
+                .filter(|item| predicate(item))
+                .collect::<Vec<_>>()
+        });
+        chunks.into_iter().flatten().collect()
+    }
+
+    fn par_reduce<F>(&self, pool: &ThreadPool, f: F) -> Option<T>
+    where
+        T: Clone + Send,
+        F: Fn(T, T) -> T + Sync,
+    {
+        let chunks = bridge(pool, self, &|chunk| chunk.iter().cloned().reduce(&f));
+        chunks.into_iter().flatten().reduce(&f)
+    }
+}
+
+/// 把切片分成若干段，在线程池上对每段调用 leaf，按段的顺序返回结果
+fn bridge<'a, T, R>(
+    pool: &ThreadPool,
+    items: &'a [T],
+    leaf: &(dyn Fn(&'a [T]) -> R + Sync),
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    let mut results = Vec::new();
+    split(pool, items, pool.num_workers(), leaf, &mut results);
+    results
+}
+
+/// 二分切片，两半分别交给线程池
+///
+/// 先无条件切到大约每个 Worker 一段；之后只有在有 Worker 空闲时才继续切，
+/// 这样均匀的任务不会被切得过碎，而耗时不均时空闲的 Worker 能通过窃取分到更小的段。
+fn split<'a, T, R>(
+    pool: &ThreadPool,
+    items: &'a [T],
+    budget: usize,
+    leaf: &(dyn Fn(&'a [T]) -> R + Sync),
+    results: &mut Vec<R>,
+) where
+    T: Sync,
+    R: Send,
+{
+    let wanted = budget > 1 || pool.shared.scheduler.idle_workers() > 0;
+    if items.len() < 2 || !wanted {
+        results.push(leaf(items));
+        return;
+    }
+    let (left, right) = items.split_at(items.len() / 2);
+    let mut right_results = Vec::new();
+    pool.scope(|s| {
+        // 右半边交给别的 Worker，左半边在当前线程上继续切
+        s.spawn(|| split(pool, right, budget / 2, leaf, &mut right_results));
+        split(pool, left, budget - budget / 2, leaf, results);
+    });
+    results.append(&mut right_results);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn map_keeps_order() {
+        let pool = ThreadPool::new(4);
+        let numbers: Vec<u64> = (0..10_000).collect();
+        let squares = numbers.par_map(&pool, |n| n * n);
+        assert_eq!(squares, numbers.iter().map(|n| n * n).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn filter_keeps_order() {
+        let pool = ThreadPool::new(4);
+        let numbers: Vec<u32> = (0..1000).collect();
+        let even = numbers.par_filter(&pool, |n| n % 2 == 0);
+        let expected: Vec<_> = numbers.iter().filter(|n| *n % 2 == 0).collect();
+        assert_eq!(even, expected);
+    }
+
+    #[test]
+    fn reduce_is_not_reordered() {
+        // 字符串拼接满足结合律但不满足交换律，顺序错了结果就不同
+        let pool = ThreadPool::new(4);
+        let words: Vec<String> = (0..200).map(|n| n.to_string()).collect();
+        let joined = words.par_reduce(&pool, |a, b| a + &b);
+        assert_eq!(joined, Some(words.concat()));
+        assert_eq!(Vec::<String>::new().par_reduce(&pool, |a, b| a + &b), None);
+    }
+
+    #[test]
+    fn for_each_visits_every_element_once() {
+        let pool = ThreadPool::new(4);
+        let seen = Mutex::new(Vec::new());
+        let items: Vec<usize> = (0..500).collect();
+        items.par_for_each(&pool, |n| seen.lock().unwrap().push(*n));
+        let mut seen = seen.into_inner().unwrap();
+        seen.sort();
+        assert_eq!(seen, items);
+    }
+
+    #[test]
+    fn uneven_work_spreads_across_workers() {
+        // 只有第一段的元素很慢，空闲的 Worker 会把剩下的部分继续切开分走
+        let pool = ThreadPool::new(4);
+        let threads = Mutex::new(HashSet::new());
+        let calls = AtomicUsize::new(0);
+        let items: Vec<usize> = (0..64).collect();
+        items.par_for_each(&pool, |n| {
+            if *n < 16 {
+                thread::sleep(Duration::from_millis(2));
+            }
+            threads.lock().unwrap().insert(thread::current().id());
+            calls.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(calls.load(Ordering::SeqCst), 64);
+        assert!(threads.into_inner().unwrap().len() > 1);
+    }
+
+    #[test]
+    fn works_from_inside_a_job() {
+        // 在 Worker 里调用时，等待的 Worker 会帮忙执行切出来的任务，不会把线程池占满
+        let pool = ThreadPool::new(2);
+        let total = Mutex::new(0);
+        pool.scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    let items: Vec<u64> = (1..=100).collect();
+                    let sum = items.par_reduce(&pool, |a, b| a + b).unwrap();
+                    *total.lock().unwrap() += sum;
+                });
+            }
+        });
+        assert_eq!(total.into_inner().unwrap(), 10_100);
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+
+use super::Job;
+
+/// 任务优先级，默认 Normal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+/// 全局队列：每个优先级一条车道
+///
+/// 出队时先看高优先级的车道；但 Normal、Low 车道的队头等待超过 aging 次出队后，会被优先处理，
+/// 所以低优先级的任务最多等待大约 aging 次出队，不会被饿死。
+/// Worker 从自己的队列取任务时不经过这里，这些出队由调度器通过 count_dispatched 计入。
+/// 出队顺序只取决于入队顺序和 aging，不依赖时间，方便测试。
+pub(crate) struct PriorityQueue {
+    // 每个元素记录入队时的出队计数，用来计算等待了多少次出队
+    lanes: [VecDeque<(Job, u64)>; 3],
+    dispatched: u64,
+    aging: u64,
+}
+
+impl PriorityQueue {
+    pub(crate) fn new(aging: u64) -> PriorityQueue {
+        PriorityQueue {
+            lanes: Default::default(),
+            dispatched: 0,
+            aging,
+        }
+    }
+
+    pub(crate) fn push(&mut self, job: Job, priority: Priority) {
+        self.lanes[priority.lane()].push_back((job, self.dispatched));
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Job> {
+        let lane = self
+            .starving_lane()
+            .or_else(|| self.lanes.iter().position(|lane| !lane.is_empty()))?;
+        self.dispatched += 1;
+        self.lanes[lane].pop_front().map(|(job, _)| job)
+    }
+
+    /// 记录在别的队列上发生的 n 次出队，它们也让排在这里的任务多等了 n 次
+    pub(crate) fn count_dispatched(&mut self, n: u64) {
+        self.dispatched += n;
+    }
+
+    /// 队头等待最久、并且已经超过 aging 的车道；High 车道本来就最先处理，不参与老化
+    fn starving_lane(&self) -> Option<usize> {
+        self.lanes
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(lane, jobs)| jobs.front().map(|(_, since)| (lane, *since)))
+            .filter(|(_, since)| self.dispatched - since >= self.aging)
+            .min_by_key(|(_, since)| *since)
+            .map(|(lane, _)| lane)
+    }
+
+    /// 有没有应该抢在 Worker 自己队列之前处理的任务：高优先级或者已经老化的任务
+    pub(crate) fn has_urgent(&self) -> bool {
+        !self.lanes[Priority::High.lane()].is_empty() || self.starving_lane().is_some()
+    }
+
+    /// 从 Normal 车道按顺序取出最多 max 个任务，交给 Worker 批量处理
+    pub(crate) fn pop_normal_batch(&mut self, max: usize) -> Vec<Job> {
+        let lane = &mut self.lanes[Priority::Normal.lane()];
+        let n = max.min(lane.len());
+        self.dispatched += n as u64;
+        lane.drain(..n).map(|(job, _)| job).collect()
+    }
+
+    pub(crate) fn normal_len(&self) -> usize {
+        self.lanes[Priority::Normal.lane()].len()
+    }
+
+    /// High 或 Low 车道里有没有任务，只有这时出队顺序才可能需要 has_urgent 来判断
+    pub(crate) fn has_prioritized(&self) -> bool {
+        !self.lanes[Priority::High.lane()].is_empty()
+            || !self.lanes[Priority::Low.lane()].is_empty()
+    }
+
+    /// 取出所有满足 f 的任务，其余任务的顺序不变
+    pub(crate) fn remove_if(&mut self, f: impl Fn(&Job) -> bool) -> Vec<Job> {
+        let mut removed = Vec::new();
+        for lane in &mut self.lanes {
+            let (matched, kept) = std::mem::take(lane)
+                .into_iter()
+                .partition(|(job, _)| f(job));
+            *lane = kept;
+            removed.extend(matched.into_iter().map(|(job, _): (Job, u64)| job));
+        }
+        removed
+    }
+
+    /// 取出最早提交的、可以丢弃的任务（任务 id 最小），must_run 的任务留在队列里
+    pub(crate) fn pop_oldest(&mut self) -> Option<Job> {
+        let (lane, index, _) = self
+            .lanes
+            .iter()
+            .enumerate()
+            .filter_map(|(lane, jobs)| {
+                let index = jobs.iter().position(|(job, _)| !job.must_run)?;
+                Some((lane, index, jobs[index].0.id))
+            })
+            .min_by_key(|(_, _, id)| *id)?;
+        self.lanes[lane].remove(index).map(|(job, _)| job)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ThreadPool;
+    use std::sync::{mpsc, Arc};
+
+    fn job(id: u64) -> Job {
+        Job {
+            id,
+            run: Box::new(|| {}),
+            token: None,
+            must_run: false,
+            label: None,
+            queued_at: std::time::Instant::now(),
+        }
+    }
+
+    fn drain(queue: &mut PriorityQueue) -> Vec<u64> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|job| job.id)
+            .collect()
+    }
+
+    #[test]
+    fn higher_lanes_first_fifo_within_lane() {
+        let mut queue = PriorityQueue::new(100);
+        queue.push(job(0), Priority::Low);
+        queue.push(job(1), Priority::Normal);
+        queue.push(job(2), Priority::High);
+        queue.push(job(3), Priority::Low);
+        queue.push(job(4), Priority::High);
+        queue.push(job(5), Priority::Normal);
+        assert_eq!(drain(&mut queue), vec![2, 4, 1, 5, 0, 3]);
+    }
+
+    #[test]
+    fn low_priority_ages_instead_of_starving() {
+        let mut queue = PriorityQueue::new(3);
+        queue.push(job(0), Priority::Low);
+        for id in 1..=6 {
+            queue.push(job(id), Priority::High);
+        }
+        // 低优先级任务等了 3 次出队后被优先处理
+        assert_eq!(drain(&mut queue), vec![1, 2, 3, 0, 4, 5, 6]);
+    }
+
+    #[test]
+    fn keeps_aging_under_continuous_high_load() {
+        let mut queue = PriorityQueue::new(2);
+        let mut order = Vec::new();
+        queue.push(job(100), Priority::Low);
+        queue.push(job(200), Priority::Normal);
+        for id in 0..6 {
+            queue.push(job(id), Priority::High);
+            order.push(queue.pop().unwrap().id);
+        }
+        // 同时老化时先处理优先级高的车道
+        assert_eq!(order, vec![0, 1, 200, 100, 2, 3]);
+    }
+
+    #[test]
+    fn pop_oldest_ignores_priority() {
+        let mut queue = PriorityQueue::new(100);
+        queue.push(job(1), Priority::High);
+        queue.push(job(0), Priority::Low);
+        assert_eq!(queue.pop_oldest().map(|job| job.id), Some(0));
+    }
+
+    #[test]
+    fn pop_oldest_keeps_must_run_jobs() {
+        let mut queue = PriorityQueue::new(100);
+        let mut pinned = job(0);
+        pinned.must_run = true;
+        queue.push(pinned, Priority::Normal);
+        queue.push(job(1), Priority::Normal);
+        assert_eq!(queue.pop_oldest().map(|job| job.id), Some(1));
+        assert!(queue.pop_oldest().is_none());
+        assert_eq!(drain(&mut queue), vec![0]);
+    }
+
+    #[test]
+    fn pool_dispatches_by_priority() {
+        let pool = ThreadPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let jobs = [
+            (Priority::Low, "low 1"),
+            (Priority::Normal, "normal 1"),
+            (Priority::High, "high 1"),
+            (Priority::Normal, "normal 2"),
+            (Priority::Low, "low 2"),
+            (Priority::High, "high 2"),
+            (Priority::Normal, "normal 3"),
+        ];
+        for (priority, name) in jobs {
+            let tx = tx.clone();
+            pool.execute_with_priority(priority, move || tx.send(name).unwrap())
+                .unwrap();
+        }
+        release_tx.send(()).unwrap();
+        drop(tx);
+        drop(pool);
+
+        let order: Vec<_> = rx.iter().collect();
+        assert_eq!(
+            order,
+            vec!["high 1", "high 2", "normal 1", "normal 2", "normal 3", "low 1", "low 2"]
+        );
+    }
+
+    #[test]
+    fn low_priority_ages_while_workers_keep_spawning() {
+        let pool = Arc::new(
+            ThreadPool::builder()
+                .num_threads(1)
+                .priority_aging(4)
+                .build()
+                .unwrap(),
+        );
+        let (tx, rx) = mpsc::channel();
+
+        // 每一步在 Worker 里提交下一步，任务一直在 Worker 自己的队列里，不经过全局队列
+        fn step(pool: Arc<ThreadPool>, tx: mpsc::Sender<&'static str>, left: usize) {
+            tx.send("step").unwrap();
+            if left > 0 {
+                let next = Arc::clone(&pool);
+                pool.execute(move || step(next, tx, left - 1)).unwrap();
+            }
+        }
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        let (chain, chain_tx) = (Arc::clone(&pool), tx.clone());
+        pool.execute(move || step(chain, chain_tx, 50)).unwrap();
+        pool.execute_with_priority(Priority::Low, move || tx.send("low").unwrap())
+            .unwrap();
+        release_tx.send(()).unwrap();
+        pool.run_until_idle();
+
+        let order: Vec<_> = rx.iter().collect();
+        assert_eq!(order.len(), 52);
+        let position = order.iter().position(|name| *name == "low").unwrap();
+        assert!(position <= 6, "low ran at {position}: {order:?}");
+    }
+}
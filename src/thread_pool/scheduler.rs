@@ -0,0 +1,725 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use super::priority::{Priority, PriorityQueue};
+use super::Job;
+
+// 从全局队列一次最多搬多少个任务到自己的队列
+const BATCH: usize = 16;
+
+thread_local! {
+    // 当前线程是哪个调度器的哪个 Worker，用来让 Worker 里提交的任务进入它自己的队列
+    static CURRENT: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+/// 有界队列满了之后如何处理新任务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// 阻塞调用方直到有空位；在 Worker 线程里调用时改为由调用方执行，避免 Worker 等待自己
+    Block,
+    /// 拒绝新任务，execute 返回 ExecuteError::QueueFull
+    Reject,
+    /// 丢弃排队最久的任务，为新任务腾出位置
+    DropOldest,
+    /// 在调用方线程上直接执行新任务；和 Worker 上一样，panic 被捕获后交给 JobHandle 和 panic 处理函数
+    CallerRuns,
+}
+
+/// 提交任务失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecuteError {
+    /// 有界队列已满，并且溢出策略是 Reject
+    QueueFull,
+}
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecuteError::QueueFull => write!(f, "job queue is full"),
+        }
+    }
+}
+
+impl Error for ExecuteError {}
+
+/// Worker 向调度器要任务的结果
+pub(crate) enum Next {
+    Job(Job),
+    /// 被唤醒但没有任务，timed_out 表示空闲超过了 keep-alive
+    Idle {
+        timed_out: bool,
+    },
+    Shutdown,
+}
+
+/// 队列放不下时交还给调用方的任务
+pub(crate) enum Overflow {
+    Rejected,
+    RunOnCaller(Job),
+}
+
+/// 工作窃取调度器
+///
+/// 每个 Worker 有自己的双端队列，从队尾取自己的任务；空闲时先从全局队列批量搬运，
+/// 再从其他 Worker 的队头窃取。线程池外部提交的任务和带优先级的任务进入全局队列，
+/// 全局队列里的高优先级任务和老化的任务会抢在 Worker 自己的队列之前处理。
+pub(crate) struct Scheduler {
+    injector: Mutex<PriorityQueue>,
+    // 全局队列里有没有非 Normal 的任务，没有时不必每次都去全局队列检查紧急任务
+    prioritized: AtomicBool,
+    // 全局队列里有非 Normal 任务时，Worker 从本地队列取走或者窃取的任务数，下次检查紧急任务时计入老化
+    bypassed: AtomicU64,
+    // 按 Worker id 索引，线程池扩容时追加
+    locals: RwLock<Vec<Mutex<VecDeque<Job>>>>,
+    // 只能由对应 Worker 执行的任务（broadcast），不计入 queued，也不会被窃取
+    pinned: RwLock<Vec<Mutex<VecDeque<Job>>>>,
+    // 所有队列里的任务总数，Worker 睡眠前用它判断是否还有活
+    queued: AtomicUsize,
+    // 正在睡眠的 Worker 数，没有人睡眠时提交任务不必去拿锁
+    sleeping: AtomicUsize,
+    shutdown: Mutex<bool>,
+    wakeup: Condvar,
+    bound: Option<(usize, OverflowPolicy)>,
+    // 有界队列满时，Block 策略的调用方在这里等待空位
+    space: Mutex<()>,
+    space_freed: Condvar,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // 任务都在 catch_unwind 中执行，持锁期间不会运行用户代码
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Scheduler {
+    pub(crate) fn new(bound: Option<(usize, OverflowPolicy)>, aging: u64) -> Scheduler {
+        Scheduler {
+            injector: Mutex::new(PriorityQueue::new(aging)),
+            prioritized: AtomicBool::new(false),
+            bypassed: AtomicU64::new(0),
+            locals: RwLock::new(Vec::new()),
+            pinned: RwLock::new(Vec::new()),
+            queued: AtomicUsize::new(0),
+            sleeping: AtomicUsize::new(0),
+            shutdown: Mutex::new(false),
+            wakeup: Condvar::new(),
+            bound,
+            space: Mutex::new(()),
+            space_freed: Condvar::new(),
+        }
+    }
+
+    fn key(&self) -> usize {
+        self as *const Scheduler as usize
+    }
+
+    /// 确保 Worker id 有自己的队列
+    pub(crate) fn add_slot(&self, id: usize) {
+        let mut locals = self.locals.write().unwrap_or_else(PoisonError::into_inner);
+        while locals.len() <= id {
+            locals.push(Mutex::new(VecDeque::new()));
+        }
+        let mut pinned = self.pinned.write().unwrap_or_else(PoisonError::into_inner);
+        while pinned.len() <= id {
+            pinned.push(Mutex::new(VecDeque::new()));
+        }
+    }
+
+    /// Worker 退休前把自己队列里的任务交还给全局队列
+    pub(crate) fn release_slot(&self, id: usize) {
+        let jobs = std::mem::take(&mut *lock(&self.locals()[id]));
+        if !jobs.is_empty() {
+            let mut injector = lock(&self.injector);
+            for job in jobs {
+                injector.push(job, Priority::Normal);
+            }
+            drop(injector);
+            self.wake_all();
+        }
+    }
+
+    fn locals(&self) -> std::sync::RwLockReadGuard<'_, Vec<Mutex<VecDeque<Job>>>> {
+        self.locals.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn pinned(&self) -> std::sync::RwLockReadGuard<'_, Vec<Mutex<VecDeque<Job>>>> {
+        self.pinned.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 把任务交给 Worker id 本人执行，不受有界队列限制
+    pub(crate) fn push_pinned(&self, id: usize, job: Job) {
+        lock(&self.pinned()[id]).push_back(job);
+        // 不知道哪个 Worker 在睡眠，全部叫醒；Worker 睡眠前在同一把锁下检查自己的 pinned 队列
+        self.wake_all();
+    }
+
+    /// Worker id 还有没有只能由它执行的任务
+    pub(crate) fn has_pinned(&self, id: usize) -> bool {
+        !lock(&self.pinned()[id]).is_empty()
+    }
+
+    /// 把当前线程登记为 Worker id
+    pub(crate) fn enter(&self, id: usize) {
+        CURRENT.with(|current| current.set(Some((self.key(), id))));
+    }
+
+    /// 当前线程是不是本调度器的 Worker
+    pub(crate) fn current_worker(&self) -> Option<usize> {
+        CURRENT
+            .with(Cell::get)
+            .filter(|(key, _)| *key == self.key())
+            .map(|(_, id)| id)
+    }
+
+    pub(crate) fn push(&self, job: Job, priority: Priority) -> Result<(), Overflow> {
+        if !self.reserve() {
+            // reserve 只有在有界模式下才会失败
+            let (_, policy) = self.bound.unwrap();
+            match policy {
+                OverflowPolicy::Reject => return Err(Overflow::Rejected),
+                OverflowPolicy::CallerRuns => return Err(Overflow::RunOnCaller(job)),
+                OverflowPolicy::Block if self.current_worker().is_some() => {
+                    return Err(Overflow::RunOnCaller(job))
+                }
+                OverflowPolicy::Block => self.wait_for_space(),
+                OverflowPolicy::DropOldest => self.make_room(),
+            }
+        }
+        self.enqueue(job, priority);
+        Ok(())
+    }
+
+    /// 不阻塞、也不交给调用方执行的入队，放不下时把任务还回去；队列满时只有 DropOldest 会腾出位置
+    pub(crate) fn try_push(&self, job: Job, priority: Priority) -> Result<(), Job> {
+        if !self.reserve() {
+            let (_, policy) = self.bound.unwrap();
+            if policy != OverflowPolicy::DropOldest {
+                return Err(job);
+            }
+            self.make_room();
+        }
+        self.enqueue(job, priority);
+        Ok(())
+    }
+
+    /// 不检查容量直接入队，用于已经被接受过的任务的后续工作
+    pub(crate) fn push_unbounded(&self, job: Job, priority: Priority) {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.enqueue(job, priority);
+    }
+
+    /// 不检查容量，排到全局队列末尾：Worker 自己的队列是后进先出，放在那里会被马上取回来
+    pub(crate) fn requeue(&self, job: Job, priority: Priority) {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.inject(job, priority);
+        self.wake_one();
+    }
+
+    fn enqueue(&self, job: Job, priority: Priority) {
+        match self.current_worker() {
+            Some(id) if priority == Priority::Normal => lock(&self.locals()[id]).push_back(job),
+            _ => self.inject(job, priority),
+        }
+        self.wake_one();
+    }
+
+    fn inject(&self, job: Job, priority: Priority) {
+        let mut injector = lock(&self.injector);
+        injector.push(job, priority);
+        self.prioritized
+            .store(injector.has_prioritized(), Ordering::SeqCst);
+    }
+
+    fn wake_one(&self) {
+        // Worker 先登记 sleeping 再检查 queued，这里先增加 queued 再检查 sleeping，两边至少有一方能看到对方。
+        // 先拿锁再通知，保证不会错过正准备睡眠的 Worker
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            let _guard = lock(&self.shutdown);
+            self.wakeup.notify_one();
+        }
+    }
+
+    /// 为一个新任务占一个名额，有界队列已满时返回 false
+    fn reserve(&self) -> bool {
+        match self.bound {
+            None => {
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+            Some((capacity, _)) => self
+                .queued
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                    (queued < capacity).then_some(queued + 1)
+                })
+                .is_ok(),
+        }
+    }
+
+    fn wait_for_space(&self) {
+        let mut guard = lock(&self.space);
+        while !self.reserve() {
+            guard = self
+                .space_freed
+                .wait(guard)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// 丢弃最老的任务并把它的名额留给新任务
+    fn make_room(&self) {
+        loop {
+            // 被丢弃的任务在锁外 drop，它持有的 JobHandle 写端会把结果标记为取消
+            if self.evict_oldest().is_some() {
+                return;
+            }
+            // 名额都被正在入队的任务占着，稍等再试
+            if self.reserve() {
+                return;
+            }
+            thread::yield_now();
+        }
+    }
+
+    // 必须执行的任务不会被丢弃
+    fn evict_oldest(&self) -> Option<Job> {
+        let oldest = lock(&self.injector).pop_oldest();
+        oldest.or_else(|| {
+            self.locals().iter().find_map(|local| {
+                let mut local = lock(local);
+                let index = local.iter().position(|job| !job.must_run)?;
+                local.remove(index)
+            })
+        })
+    }
+
+    /// 取出所有排队中、可以取消的任务，交给调用方在锁外 drop
+    pub(crate) fn remove_cancellable(&self) -> Vec<Job> {
+        let mut injector = lock(&self.injector);
+        let mut removed = injector.remove_if(|job| !job.must_run);
+        self.prioritized
+            .store(injector.has_prioritized(), Ordering::SeqCst);
+        drop(injector);
+        for local in self.locals().iter() {
+            let mut local = lock(local);
+            let (matched, kept): (VecDeque<Job>, VecDeque<Job>) = std::mem::take(&mut *local)
+                .into_iter()
+                .partition(|job| !job.must_run);
+            *local = kept;
+            removed.extend(matched);
+        }
+        if !removed.is_empty() {
+            self.queued.fetch_sub(removed.len(), Ordering::SeqCst);
+            if self.bound.is_some() {
+                let _guard = lock(&self.space);
+                self.space_freed.notify_all();
+            }
+        }
+        removed
+    }
+
+    /// 排队中的任务数
+    pub(crate) fn len(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// 正在等待任务的 Worker 数
+    pub(crate) fn idle_workers(&self) -> usize {
+        self.sleeping.load(Ordering::SeqCst)
+    }
+
+    /// 不睡眠地取一个任务，Worker 在 scope 里等待时用它帮忙干活
+    pub(crate) fn find_job(&self, id: usize) -> Option<Job> {
+        if let Some(job) = lock(&self.pinned()[id]).pop_front() {
+            return Some(job);
+        }
+        let job = self
+            .pop_urgent()
+            .or_else(|| self.pop_local(id))
+            .or_else(|| self.pop_injector(id))
+            .or_else(|| self.steal(id));
+        if job.is_some() {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            if self.bound.is_some() {
+                let _guard = lock(&self.space);
+                self.space_freed.notify_one();
+            }
+        }
+        job
+    }
+
+    fn pop_local(&self, id: usize) -> Option<Job> {
+        let job = lock(&self.locals()[id]).pop_back();
+        self.count_bypass(&job);
+        job
+    }
+
+    // 一直在处理自己队列的 Worker 也要让全局队列里的任务老化，否则 Low 任务照样会被饿死
+    fn count_bypass(&self, job: &Option<Job>) {
+        if job.is_some() && self.prioritized.load(Ordering::SeqCst) {
+            self.bypassed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn pop_urgent(&self) -> Option<Job> {
+        if !self.prioritized.load(Ordering::SeqCst) {
+            return None;
+        }
+        let mut injector = lock(&self.injector);
+        injector.count_dispatched(self.bypassed.swap(0, Ordering::SeqCst));
+        let job = if injector.has_urgent() {
+            injector.pop()
+        } else {
+            None
+        };
+        self.prioritized
+            .store(injector.has_prioritized(), Ordering::SeqCst);
+        job
+    }
+
+    // 锁的顺序总是先全局队列再 Worker 队列
+    fn pop_injector(&self, id: usize) -> Option<Job> {
+        let mut injector = lock(&self.injector);
+        let job = injector.pop()?;
+        self.prioritized
+            .store(injector.has_prioritized(), Ordering::SeqCst);
+        if injector.has_urgent() {
+            return Some(job);
+        }
+        let locals = self.locals();
+        // 多搬几个 Normal 任务到自己的队列，减少对全局队列的争用；但只搬平均份额，剩下的留给别的 Worker
+        let share = (injector.normal_len() / locals.len()).min(BATCH);
+        if share > 0 {
+            // Worker 从队尾取任务，倒序放入才能保持提交顺序
+            let batch = injector.pop_normal_batch(share);
+            lock(&locals[id]).extend(batch.into_iter().rev());
+        }
+        Some(job)
+    }
+
+    fn steal(&self, id: usize) -> Option<Job> {
+        let locals = self.locals();
+        let n = locals.len();
+        let job = (1..n)
+            .map(|offset| (id + offset) % n)
+            .find_map(|victim| lock(&locals[victim]).pop_front());
+        self.count_bypass(&job);
+        job
+    }
+
+    /// 取下一个任务，没有任务时最多睡眠 keep_alive（None 表示一直等）
+    pub(crate) fn next_job(&self, id: usize, keep_alive: Option<Duration>) -> Next {
+        loop {
+            if let Some(job) = self.find_job(id) {
+                return Next::Job(job);
+            }
+            let shutdown = lock(&self.shutdown);
+            self.sleeping.fetch_add(1, Ordering::SeqCst);
+            if self.len() > 0 || self.has_pinned(id) {
+                self.sleeping.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+            if *shutdown {
+                self.sleeping.fetch_sub(1, Ordering::SeqCst);
+                return Next::Shutdown;
+            }
+            let timed_out = match keep_alive {
+                Some(timeout) => self
+                    .wakeup
+                    .wait_timeout(shutdown, timeout)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .1
+                    .timed_out(),
+                None => {
+                    drop(
+                        self.wakeup
+                            .wait(shutdown)
+                            .unwrap_or_else(PoisonError::into_inner),
+                    );
+                    false
+                }
+            };
+            self.sleeping.fetch_sub(1, Ordering::SeqCst);
+            if self.len() == 0 && !self.has_pinned(id) {
+                return Next::Idle { timed_out };
+            }
+        }
+    }
+
+    /// 唤醒所有睡眠的 Worker，让它们重新检查自己是否该退休
+    pub(crate) fn wake_all(&self) {
+        let _guard = lock(&self.shutdown);
+        self.wakeup.notify_all();
+    }
+
+    /// 通知 Worker 把剩下的任务做完后退出
+    pub(crate) fn shutdown(&self) {
+        *lock(&self.shutdown) = true;
+        self.wakeup.notify_all();
+    }
+
+    pub(crate) fn is_shutdown(&self) -> bool {
+        *lock(&self.shutdown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecuteError, OverflowPolicy};
+    use crate::thread_pool::{JobError, JobHandle};
+    use crate::ThreadPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{self, Receiver};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // 原来的设计：所有 Worker 争抢同一个 Mutex<Receiver>，作为基准测试的对照组
+    struct ChannelPool {
+        workers: Vec<thread::JoinHandle<()>>,
+        sender: Option<mpsc::Sender<Box<dyn FnOnce() + Send>>>,
+    }
+
+    impl ChannelPool {
+        fn new(size: usize) -> ChannelPool {
+            let (sender, receiver) = mpsc::channel::<Box<dyn FnOnce() + Send>>();
+            let receiver: Arc<Mutex<Receiver<_>>> = Arc::new(Mutex::new(receiver));
+            let workers = (0..size)
+                .map(|_| Arc::clone(&receiver))
+                .map(|receiver| {
+                    thread::spawn(move || loop {
+                        let message = receiver.lock().unwrap().recv();
+                        match message {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                })
+                .collect();
+            ChannelPool {
+                workers,
+                sender: Some(sender),
+            }
+        }
+    }
+
+    impl Drop for ChannelPool {
+        fn drop(&mut self) {
+            drop(self.sender.take());
+            for worker in self.workers.drain(..) {
+                worker.join().unwrap();
+            }
+        }
+    }
+
+    type Spawn = Arc<dyn Fn(Box<dyn FnOnce() + Send>) + Send + Sync>;
+
+    const WORKERS: usize = 4;
+    const JOBS: usize = 100_000;
+
+    fn wait_for(counter: &AtomicUsize, total: usize) {
+        while counter.load(Ordering::Relaxed) < total {
+            thread::yield_now();
+        }
+    }
+
+    // 调用方线程连续提交大量小任务
+    fn tiny_jobs(spawn: &Spawn) -> Duration {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let start = Instant::now();
+        for _ in 0..JOBS {
+            let counter = Arc::clone(&counter);
+            spawn(Box::new(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+        wait_for(&counter, JOBS);
+        start.elapsed()
+    }
+
+    // 任务在 Worker 里继续拆分出小任务
+    fn fan_out(spawn: &Spawn) -> Duration {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let start = Instant::now();
+        for _ in 0..JOBS / 1000 {
+            let counter = Arc::clone(&counter);
+            let inner = Arc::clone(spawn);
+            spawn(Box::new(move || {
+                for _ in 0..1000 {
+                    let counter = Arc::clone(&counter);
+                    inner(Box::new(move || {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    }));
+                }
+            }));
+        }
+        wait_for(&counter, JOBS);
+        start.elapsed()
+    }
+
+    #[test]
+    fn jobs_spawned_inside_workers_all_run() {
+        let pool = Arc::new(ThreadPool::new(WORKERS));
+        let counter = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let inner_pool = Arc::clone(&pool);
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                for _ in 0..100 {
+                    let counter = Arc::clone(&counter);
+                    inner_pool
+                        .execute(move || {
+                            counter.fetch_add(1, Ordering::SeqCst);
+                        })
+                        .unwrap();
+                }
+            })
+            .unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while counter.load(Ordering::SeqCst) < 100 * 100 {
+            assert!(Instant::now() < deadline, "jobs were lost");
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn idle_workers_steal_from_busy_ones() {
+        // 一个 Worker 在自己的队列里塞满任务后阻塞，其他 Worker 必须把它们偷走
+        let pool = Arc::new(ThreadPool::new(WORKERS));
+        let (tx, rx) = mpsc::channel();
+        let inner_pool = Arc::clone(&pool);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            for i in 0..10 {
+                let tx = tx.clone();
+                inner_pool.execute(move || tx.send(i).unwrap()).unwrap();
+            }
+            release_rx.recv().unwrap();
+        })
+        .unwrap();
+        let mut got: Vec<i32> = (0..10)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        got.sort();
+        assert_eq!(got, (0..10).collect::<Vec<_>>());
+        release_tx.send(()).unwrap();
+    }
+
+    // 单 Worker 被一个任务占住，队列此时为空
+    fn busy_pool(capacity: usize, policy: OverflowPolicy) -> (ThreadPool, mpsc::Sender<()>) {
+        let pool = ThreadPool::builder()
+            .num_threads(1)
+            .bounded(capacity, policy)
+            .build()
+            .unwrap();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        (pool, release_tx)
+    }
+
+    #[test]
+    fn reject_when_full() {
+        let (pool, release) = busy_pool(1, OverflowPolicy::Reject);
+        let handle = pool.submit(|| 1);
+        assert_eq!(pool.execute(|| {}), Err(ExecuteError::QueueFull));
+        release.send(()).unwrap();
+        assert_eq!(handle.join(), Ok(1));
+        assert_eq!(pool.execute(|| {}), Ok(()));
+    }
+
+    #[test]
+    fn drop_oldest_when_full() {
+        let (pool, release) = busy_pool(2, OverflowPolicy::DropOldest);
+        let handles: Vec<_> = (0..4).map(|i| pool.submit(move || i)).collect();
+        release.send(()).unwrap();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join()).collect();
+        assert_eq!(
+            results,
+            vec![
+                Err(JobError::Cancelled),
+                Err(JobError::Cancelled),
+                Ok(2),
+                Ok(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn caller_runs_when_full() {
+        let (pool, release) = busy_pool(1, OverflowPolicy::CallerRuns);
+        pool.execute(|| {}).unwrap();
+        let (tx, rx) = mpsc::channel();
+        pool.execute(move || tx.send(thread::current().id()).unwrap())
+            .unwrap();
+        assert_eq!(rx.try_recv(), Ok(thread::current().id()));
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn caller_runs_reports_panics_through_the_handle() {
+        let (pool, release) = busy_pool(1, OverflowPolicy::CallerRuns);
+        pool.execute(|| {}).unwrap();
+        let handle: JobHandle<()> = pool.submit(|| panic!("on the caller"));
+        assert_eq!(
+            handle.join(),
+            Err(JobError::Panicked("on the caller".to_string()))
+        );
+        release.send(()).unwrap();
+        pool.run_until_idle();
+        let stats = pool.stats();
+        assert_eq!(stats.panicked, 1);
+        assert_eq!(stats.completed, 3);
+    }
+
+    #[test]
+    fn block_until_space() {
+        let (pool, release) = busy_pool(1, OverflowPolicy::Block);
+        let pool = Arc::new(pool);
+        pool.execute(|| {}).unwrap();
+
+        let (done_tx, done_rx) = mpsc::channel();
+        let producer = {
+            let pool = Arc::clone(&pool);
+            thread::spawn(move || {
+                pool.execute(|| {}).unwrap();
+                done_tx.send(()).unwrap();
+            })
+        };
+        assert!(done_rx.recv_timeout(Duration::from_millis(50)).is_err());
+        release.send(()).unwrap();
+        done_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        producer.join().unwrap();
+    }
+
+    /// cargo test -- --ignored --nocapture 查看对比结果
+    #[test]
+    #[ignore]
+    fn bench_against_shared_receiver() {
+        let pool = Arc::new(ThreadPool::new(WORKERS));
+        let spawn: Spawn = Arc::new(move |job| pool.execute(job).unwrap());
+        let stealing = (tiny_jobs(&spawn), fan_out(&spawn));
+        drop(spawn);
+
+        let pool = ChannelPool::new(WORKERS);
+        let sender = pool.sender.clone().unwrap();
+        let spawn: Spawn = Arc::new(move |job| sender.send(job).unwrap());
+        let channel = (tiny_jobs(&spawn), fan_out(&spawn));
+        drop(spawn);
+        drop(pool);
+
+        println!("{JOBS} tiny jobs on {WORKERS} workers (submitted from caller / from workers):");
+        println!("  work stealing:   {:?} / {:?}", stealing.0, stealing.1);
+        println!("  Mutex<Receiver>: {:?} / {:?}", channel.0, channel.1);
+    }
+}
@@ -0,0 +1,334 @@
+use std::any::Any;
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use super::{Job, Priority, Shared};
+
+/// pool.scope 里的作用域，用来提交可以借用外部数据的任务
+///
+/// 和 std::thread::scope 一样，'env 是被借用数据的生命周期，'scope 是作用域本身的生命周期；
+/// 任务跑在线程池已有的 Worker 上，scope 返回前会等待所有任务结束。
+pub struct Scope<'scope, 'env: 'scope> {
+    shared: Arc<Shared>,
+    state: Arc<ScopeState>,
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+#[derive(Default)]
+struct ScopeState {
+    progress: Mutex<Progress>,
+    finished: Condvar,
+}
+
+#[derive(Default)]
+struct Progress {
+    pending: usize,
+    // 第一个 panic 的任务，scope 结束时在调用方重新抛出
+    panic: Option<Box<dyn Any + Send>>,
+    // 被有界队列的 DropOldest 策略丢弃、没有执行的任务数
+    discarded: usize,
+}
+
+impl ScopeState {
+    fn lock(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// 作用域里的一个任务：不论执行完还是没执行就被丢弃，都会在释放借用之后才计数减一
+struct ScopedJob {
+    f: Option<Box<dyn FnOnce() + Send + 'static>>,
+    state: Arc<ScopeState>,
+}
+
+impl ScopedJob {
+    fn run(mut self) {
+        let f = self.f.take().unwrap();
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            self.state.lock().panic.get_or_insert(payload);
+        }
+    }
+}
+
+impl Drop for ScopedJob {
+    fn drop(&mut self) {
+        let discarded = self.f.take().is_some();
+        let mut progress = self.state.lock();
+        if discarded {
+            progress.discarded += 1;
+        }
+        progress.pending -= 1;
+        if progress.pending == 0 {
+            self.state.finished.notify_all();
+        }
+    }
+}
+
+impl<'scope> Scope<'scope, '_> {
+    /// 提交一个任务，它可以借用 scope 之外的数据
+    ///
+    /// 有界队列拒绝时任务在调用方线程上直接执行。
+    pub fn spawn<F>(&'scope self, f: F)
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        let (pooled, slot) = self.scoped_job(Box::new(f));
+        let result = self.shared.push(pooled, Priority::Normal);
+        if result.is_err() {
+            // 被拒绝的闭包已经 drop，任务还留在 slot 里
+            let job = slot.lock().unwrap_or_else(PoisonError::into_inner).take();
+            if let Some(job) = job {
+                job.run();
+            }
+        }
+    }
+
+    /// 提交一个只能由 Worker worker_id 执行的任务，broadcast 用
+    pub(crate) fn spawn_pinned<F>(&'scope self, worker_id: usize, f: F)
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        let (pooled, _) = self.scoped_job(Box::new(f));
+        self.shared.scheduler.push_pinned(worker_id, pooled);
+    }
+
+    /// 把 f 包装成线程池的任务；任务被拒绝时 ScopedJob 还留在返回的 slot 里
+    fn scoped_job(
+        &'scope self,
+        f: Box<dyn FnOnce() + Send + 'scope>,
+    ) -> (Job, Arc<Mutex<Option<ScopedJob>>>) {
+        // SAFETY: scope 在返回之前会等到所有 ScopedJob 被执行或者丢弃，
+        // 而 ScopedJob 在计数减一之前就已经释放了 f，所以 f 不会活得比 'scope 更久。
+        let f: Box<dyn FnOnce() + Send + 'static> = unsafe { mem::transmute(f) };
+
+        self.state.lock().pending += 1;
+        let job = ScopedJob {
+            f: Some(f),
+            state: Arc::clone(&self.state),
+        };
+        let slot = Arc::new(Mutex::new(Some(job)));
+        let queued = Arc::clone(&slot);
+        let mut pooled = self.shared.new_job(Box::new(move || {
+            let job = queued.lock().unwrap_or_else(PoisonError::into_inner).take();
+            if let Some(job) = job {
+                job.run();
+            }
+        }));
+        pooled.must_run = true;
+        (pooled, slot)
+    }
+
+    /// 等待所有任务结束；在本线程池的 Worker 上调用时，一边等一边执行排队的任务，避免 Worker 互相等待
+    fn wait(&self) {
+        let worker = self.shared.scheduler.current_worker();
+        let mut progress = self.state.lock();
+        while progress.pending > 0 {
+            match worker {
+                Some(id) => {
+                    drop(progress);
+                    match self.shared.scheduler.find_job(id) {
+                        Some(job) => self.shared.run_job(id, job),
+                        // 剩下的任务正在别的 Worker 上执行，短暂等待后再看看有没有新任务
+                        None => {
+                            let progress = self.state.lock();
+                            if progress.pending > 0 {
+                                drop(
+                                    self.state
+                                        .finished
+                                        .wait_timeout(progress, Duration::from_millis(1))
+                                        .unwrap_or_else(PoisonError::into_inner),
+                                );
+                            }
+                        }
+                    }
+                    progress = self.state.lock();
+                }
+                // 确定性线程池没有 Worker，任务只能在这里执行
+                None if self.shared.deterministic.is_some() => {
+                    drop(progress);
+                    if !self.shared.run_next_deterministic() {
+                        // 剩下的任务还在定时器里或者别的线程上
+                        let progress = self.state.lock();
+                        if progress.pending > 0 {
+                            drop(
+                                self.state
+                                    .finished
+                                    .wait_timeout(progress, Duration::from_millis(1))
+                                    .unwrap_or_else(PoisonError::into_inner),
+                            );
+                        }
+                    }
+                    progress = self.state.lock();
+                }
+                None => {
+                    progress = self
+                        .state
+                        .finished
+                        .wait(progress)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+}
+
+impl Shared {
+    pub(crate) fn scope<'env, F, T>(self: &Arc<Self>, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        let scope = Scope {
+            shared: Arc::clone(self),
+            state: Arc::new(ScopeState::default()),
+            scope: PhantomData,
+            env: PhantomData,
+        };
+        // f 自己 panic 时也要等已经提交的任务结束，否则它们会访问已经失效的借用
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.wait();
+
+        let mut progress = scope.state.lock();
+        let result = match result {
+            Ok(value) => value,
+            Err(payload) => panic::resume_unwind(payload),
+        };
+        if let Some(payload) = progress.panic.take() {
+            drop(progress);
+            panic::resume_unwind(payload);
+        }
+        if progress.discarded > 0 {
+            panic!(
+                "{} scoped jobs were discarded by the bounded queue",
+                progress.discarded
+            );
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_pool::OverflowPolicy;
+    use crate::ThreadPool;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn jobs_borrow_stack_data() {
+        let pool = ThreadPool::new(4);
+        let numbers: Vec<u64> = (1..=1000).collect();
+        let mut sums = [0u64; 4];
+        pool.scope(|s| {
+            for (chunk, sum) in numbers.chunks(250).zip(sums.iter_mut()) {
+                s.spawn(move || *sum = chunk.iter().sum());
+            }
+        });
+        assert_eq!(sums.iter().sum::<u64>(), 500_500);
+    }
+
+    #[test]
+    fn waits_for_every_job() {
+        let pool = ThreadPool::new(2);
+        let done = AtomicUsize::new(0);
+        pool.scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    thread::sleep(Duration::from_millis(10));
+                    done.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(done.load(Ordering::SeqCst), 8);
+    }
+
+    #[test]
+    fn runs_on_existing_workers() {
+        let pool = ThreadPool::builder()
+            .num_threads(2)
+            .thread_name("scoped")
+            .build()
+            .unwrap();
+        let names = Mutex::new(Vec::new());
+        pool.scope(|s| {
+            for _ in 0..10 {
+                s.spawn(|| {
+                    let name = thread::current().name().map(String::from);
+                    names.lock().unwrap().push(name);
+                });
+            }
+        });
+        for name in names.into_inner().unwrap() {
+            let name = name.unwrap();
+            assert!(name == "scoped-0" || name == "scoped-1", "{name}");
+        }
+    }
+
+    #[test]
+    fn nested_spawns_and_scopes_inside_workers() {
+        // 只有一个 Worker：外层任务里的 scope 必须自己执行内层任务，否则会一直等下去
+        let pool = ThreadPool::new(1);
+        let count = AtomicUsize::new(0);
+        pool.scope(|s| {
+            s.spawn(|| {
+                pool.scope(|inner| {
+                    for _ in 0..4 {
+                        inner.spawn(|| {
+                            count.fetch_add(1, Ordering::SeqCst);
+                        });
+                    }
+                });
+                s.spawn(|| {
+                    count.fetch_add(10, Ordering::SeqCst);
+                });
+            });
+        });
+        assert_eq!(count.load(Ordering::SeqCst), 14);
+    }
+
+    #[test]
+    fn job_panic_propagates_after_all_jobs_finish() {
+        let pool = ThreadPool::new(2);
+        let done = AtomicUsize::new(0);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                s.spawn(|| panic!("scoped job failed"));
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        thread::sleep(Duration::from_millis(10));
+                        done.fetch_add(1, Ordering::SeqCst);
+                    });
+                }
+            })
+        }));
+        let payload = result.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"scoped job failed"));
+        assert_eq!(done.load(Ordering::SeqCst), 4);
+        // 线程池还能继续用
+        assert_eq!(pool.submit(|| 1).join(), Ok(1));
+    }
+
+    #[test]
+    fn rejected_jobs_run_on_caller() {
+        let pool = ThreadPool::builder()
+            .num_threads(1)
+            .bounded(1, OverflowPolicy::Reject)
+            .build()
+            .unwrap();
+        let count = AtomicUsize::new(0);
+        pool.scope(|s| {
+            for _ in 0..20 {
+                s.spawn(|| {
+                    count.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(count.load(Ordering::SeqCst), 20);
+    }
+}
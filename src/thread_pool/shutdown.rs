@@ -0,0 +1,203 @@
+use std::sync::atomic::Ordering;
+use std::sync::PoisonError;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{PoolEvent, Shared};
+
+/// drop 线程池时使用的关闭方式：做完排队的任务，最多等这么久
+pub(crate) const DROP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 关闭线程池时如何处理还在排队的任务
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// 做完所有排队的任务再退出；到了截止时间还没做完的任务会被丢弃
+    Drain,
+    /// 丢弃所有排队的任务，只等正在执行的任务结束
+    DiscardPending,
+}
+
+/// 关闭线程池的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// 线程池从创建到关闭一共执行完的任务数
+    pub completed: u64,
+    /// 没有执行就被丢弃的任务数
+    pub discarded: usize,
+    /// 到截止时间还没退出的 Worker id，这些线程不再被 join，会在手头的任务结束后自行退出
+    pub unfinished_workers: Vec<usize>,
+}
+
+impl Shared {
+    pub(crate) fn shutdown(&self, mode: ShutdownMode, deadline: Instant) -> ShutdownReport {
+        let mut report = ShutdownReport::default();
+        // 先停掉定时器，还没到期的任务直接丢弃
+        self.timer.shutdown();
+        if mode == ShutdownMode::DiscardPending {
+            report.discarded += self.remove_cancellable().len() + self.keyed.clear();
+        }
+        self.scheduler.shutdown();
+        if self.deterministic.is_some() {
+            // 没有 Worker 可等，剩下的任务在调用方线程上做完
+            report.discarded += self.drain_deterministic(deadline);
+        }
+
+        let mut past_deadline = false;
+        // 被 join 的 Worker 可能在退出前补上新的 Worker，所以每次都重新取。
+        loop {
+            let mut worker = {
+                let mut workers = self.workers.lock().unwrap_or_else(PoisonError::into_inner);
+                if workers.is_empty() {
+                    break;
+                }
+                workers.remove(0)
+            };
+            self.emit(PoolEvent::JoiningWorker {
+                worker_id: worker.id,
+            });
+            // 如果 Worker 存放的是 Option<thread::JoinHandle<()>，就可以在 Option 上调用 take 方法将值从 Some 成员中移动出来而对 None 成员不做处理。
+            let Some(thread) = worker.thread.take() else {
+                continue;
+            };
+            // 最后一个引用可能在某个任务里被释放，线程不能 join 自己
+            if thread.thread().id() == thread::current().id() {
+                continue;
+            }
+            while !thread.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+            }
+            if thread.is_finished() {
+                // 已经 panic 的线程 join 会返回 Err，它的替代者会在后面被 join。
+                let _ = thread.join();
+                continue;
+            }
+            if !past_deadline {
+                // 超时了：剩下的任务不再执行，让卡住的 Worker 做完手头的任务后直接退出
+                past_deadline = true;
+                report.discarded += self.remove_cancellable().len() + self.keyed.clear();
+            }
+            self.emit(PoolEvent::WorkerDetached {
+                worker_id: worker.id,
+            });
+            report.unfinished_workers.push(worker.id);
+        }
+
+        // 看门狗最后停，等待 Worker 退出期间卡住的任务也能被报告
+        self.watchdog.shutdown();
+        report.completed = self.metrics.completed.load(Ordering::SeqCst);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ThreadPool;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{mpsc, Arc};
+
+    fn slow_jobs(pool: &ThreadPool, n: usize, each: Duration) {
+        for _ in 0..n {
+            pool.execute(move || thread::sleep(each)).unwrap();
+        }
+    }
+
+    #[test]
+    fn drain_runs_every_queued_job() {
+        let pool = ThreadPool::new(2);
+        slow_jobs(&pool, 10, Duration::from_millis(5));
+        let report = pool.shutdown(ShutdownMode::Drain, Instant::now() + Duration::from_secs(5));
+        assert_eq!(
+            report,
+            ShutdownReport {
+                completed: 10,
+                discarded: 0,
+                unfinished_workers: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn discard_pending_only_waits_for_running_jobs() {
+        let pool = ThreadPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        let handles: Vec<_> = (0..5).map(|i| pool.submit(move || i)).collect();
+
+        let report = pool.shutdown(
+            ShutdownMode::DiscardPending,
+            Instant::now() + Duration::from_secs(5),
+        );
+        assert_eq!((report.completed, report.discarded), (1, 5));
+        assert!(report.unfinished_workers.is_empty());
+        for handle in handles {
+            assert!(handle.join().is_err());
+        }
+    }
+
+    #[test]
+    fn discard_pending_drops_keyed_jobs() {
+        let pool = ThreadPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        let ran = Arc::new(AtomicUsize::new(0));
+        for i in 0..10 {
+            let ran = Arc::clone(&ran);
+            pool.execute_keyed(i % 2, move || {
+                ran.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+
+        let report = pool.shutdown(
+            ShutdownMode::DiscardPending,
+            Instant::now() + Duration::from_secs(5),
+        );
+        assert_eq!(report.discarded, 10);
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn stuck_worker_is_reported_after_deadline() {
+        let pool = ThreadPool::new(1);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        slow_jobs(&pool, 10, Duration::from_millis(5));
+        for i in 0..3 {
+            pool.execute_keyed(i, || thread::sleep(Duration::from_millis(5)))
+                .unwrap();
+        }
+
+        let start = Instant::now();
+        let report = pool.shutdown(
+            ShutdownMode::Drain,
+            Instant::now() + Duration::from_millis(50),
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            report,
+            ShutdownReport {
+                completed: 0,
+                discarded: 13,
+                unfinished_workers: vec![0],
+            }
+        );
+        release_tx.send(()).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::BTreeSet;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use super::{PoolEvent, Shared, Worker};
+
+/// Worker 数量的上下限和当前在岗的 Worker
+pub(crate) struct Sizing {
+    state: Mutex<SizingState>,
+    // state 的镜像，让提交任务和执行任务的热路径不必拿锁
+    live: AtomicUsize,
+    max: AtomicUsize,
+    // 空闲超过这么久的 Worker 会退休，直到只剩 min 个；None 表示不退休
+    pub(crate) keep_alive: Option<Duration>,
+}
+
+struct SizingState {
+    min: usize,
+    max: usize,
+    live: BTreeSet<usize>,
+}
+
+impl Sizing {
+    pub(crate) fn new(min: usize, max: usize, keep_alive: Option<Duration>) -> Sizing {
+        Sizing {
+            state: Mutex::new(SizingState {
+                min,
+                max,
+                live: BTreeSet::new(),
+            }),
+            live: AtomicUsize::new(0),
+            max: AtomicUsize::new(max),
+            keep_alive,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SizingState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 当前在岗的 Worker 数
+    pub(crate) fn live(&self) -> usize {
+        self.live.load(Ordering::SeqCst)
+    }
+}
+
+impl Shared {
+    /// 用最小的空闲 id 启动一个 Worker
+    fn spawn_worker(self: &Arc<Self>, state: &mut SizingState) -> io::Result<()> {
+        let id = (0..).find(|id| !state.live.contains(id)).unwrap();
+        self.scheduler.add_slot(id);
+        let worker = Worker::new(id, Arc::clone(self))?;
+        state.live.insert(id);
+        self.sizing.live.store(state.live.len(), Ordering::SeqCst);
+
+        let mut workers = self.workers.lock().unwrap_or_else(PoisonError::into_inner);
+        // 已经退休的 Worker 不需要再 join
+        workers.retain(|worker| !worker.is_finished());
+        workers.push(worker);
+        Ok(())
+    }
+
+    /// 对每个在岗的 Worker id 调用 f，期间 Worker 不会退休
+    pub(crate) fn for_each_live_worker(&self, mut f: impl FnMut(usize)) {
+        let state = self.sizing.lock();
+        for &id in &state.live {
+            f(id);
+        }
+    }
+
+    /// 启动 min 个 Worker
+    pub(crate) fn start_workers(self: &Arc<Self>) -> io::Result<()> {
+        let mut state = self.sizing.lock();
+        while state.live.len() < state.min {
+            self.spawn_worker(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// 有任务在排队、却没有空闲的 Worker 时扩容，最多到 max 个
+    pub(crate) fn maybe_grow(self: &Arc<Self>) {
+        if self.scheduler.idle_workers() > 0
+            || self.scheduler.len() == 0
+            || self.sizing.live() >= self.sizing.max.load(Ordering::SeqCst)
+        {
+            return;
+        }
+        let mut state = self.sizing.lock();
+        if state.live.len() < state.max && !self.scheduler.is_shutdown() {
+            if let Err(err) = self.spawn_worker(&mut state) {
+                self.emit(PoolEvent::SpawnFailed {
+                    worker_id: None,
+                    error: err.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Worker 检查自己是否该退休，返回 true 时 Worker 应当退出
+    ///
+    /// 数量超过 max（被 resize 缩小）时立即退休；空闲超时并且数量超过 min 时也退休。
+    pub(crate) fn should_retire(&self, id: usize, timed_out: bool) -> bool {
+        if !timed_out && self.sizing.live() <= self.sizing.max.load(Ordering::SeqCst) {
+            return false;
+        }
+        let mut state = self.sizing.lock();
+        let live = state.live.len();
+        // 还有 broadcast 的任务等着它，先不退休
+        if self.scheduler.has_pinned(id) {
+            return false;
+        }
+        if live > state.max || (timed_out && live > state.min) {
+            state.live.remove(&id);
+            self.sizing.live.store(state.live.len(), Ordering::SeqCst);
+            self.scheduler.release_slot(id);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 把 Worker 数量固定为 n：不够的立即补上，多出的做完手头的任务后退休
+    pub(crate) fn resize(self: &Arc<Self>, n: usize) -> io::Result<()> {
+        let mut state = self.sizing.lock();
+        state.min = n;
+        state.max = n;
+        self.sizing.max.store(n, Ordering::SeqCst);
+        while state.live.len() < n {
+            self.spawn_worker(&mut state)?;
+        }
+        if state.live.len() > n {
+            self.scheduler.wake_all();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_pool::PoolCreationError;
+    use crate::ThreadPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    // 提交 n 个互相等待的任务，只有同时有 n 个 Worker 时才能全部完成
+    fn run_concurrently(pool: &ThreadPool, n: usize) {
+        let barrier = Arc::new(Barrier::new(n));
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..n {
+            let barrier = Arc::clone(&barrier);
+            let tx = tx.clone();
+            pool.execute(move || {
+                barrier.wait();
+                tx.send(()).unwrap();
+            })
+            .unwrap();
+        }
+        for _ in 0..n {
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+    }
+
+    #[test]
+    fn grows_when_queue_backs_up() {
+        let pool = ThreadPool::builder()
+            .min_threads(1)
+            .max_threads(4)
+            .build()
+            .unwrap();
+        assert_eq!(pool.num_workers(), 1);
+        run_concurrently(&pool, 4);
+        assert_eq!(pool.num_workers(), 4);
+    }
+
+    #[test]
+    fn idle_workers_retire_to_min() {
+        let pool = ThreadPool::builder()
+            .min_threads(1)
+            .max_threads(4)
+            .keep_alive(Duration::from_millis(20))
+            .build()
+            .unwrap();
+        run_concurrently(&pool, 4);
+        wait_until(|| pool.num_workers() == 1);
+        // 缩容之后还能再扩容
+        run_concurrently(&pool, 3);
+    }
+
+    #[test]
+    fn resize_at_runtime() {
+        let pool = ThreadPool::new(2);
+        pool.resize(4).unwrap();
+        assert_eq!(pool.num_workers(), 4);
+        run_concurrently(&pool, 4);
+
+        pool.resize(1).unwrap();
+        wait_until(|| pool.num_workers() == 1);
+        assert_eq!(pool.submit(|| 7).join(), Ok(7));
+
+        assert!(matches!(
+            pool.resize(0),
+            Err(PoolCreationError::ZeroThreads)
+        ));
+    }
+
+    #[test]
+    fn drop_joins_every_thread() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        let pool = {
+            let started = Arc::clone(&started);
+            let stopped = Arc::clone(&stopped);
+            ThreadPool::builder()
+                .min_threads(1)
+                .max_threads(3)
+                .keep_alive(Duration::from_millis(10))
+                .on_thread_start(move |_| {
+                    started.fetch_add(1, Ordering::SeqCst);
+                })
+                .on_thread_stop(move |_| {
+                    stopped.fetch_add(1, Ordering::SeqCst);
+                })
+                .build()
+                .unwrap()
+        };
+        run_concurrently(&pool, 3);
+        pool.resize(2).unwrap();
+        drop(pool);
+        assert_eq!(
+            started.load(Ordering::SeqCst),
+            stopped.load(Ordering::SeqCst)
+        );
+    }
+}
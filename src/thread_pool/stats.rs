@@ -0,0 +1,468 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+use std::time::Duration;
+
+use super::{PoolEvent, Shared, ThreadPool};
+
+// 直方图的桶上界：10µs 到 10s，每档乘 10
+const BUCKETS: [Duration; 7] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+    Duration::from_secs(10),
+];
+
+/// 线程池内部的计数器，都是原子操作，执行任务的热路径上不拿锁
+#[derive(Default)]
+pub(crate) struct Metrics {
+    pub(crate) submitted: AtomicU64,
+    pub(crate) completed: AtomicU64,
+    pub(crate) panicked: AtomicU64,
+    pub(crate) rejected: AtomicU64,
+    wait_time: Histogram,
+    run_time: Histogram,
+    // 按 Worker id 索引，id 被重用时接着累加
+    workers: Mutex<Vec<Arc<WorkerCounters>>>,
+}
+
+#[derive(Default)]
+pub(crate) struct WorkerCounters {
+    busy_nanos: AtomicU64,
+    idle_nanos: AtomicU64,
+    jobs: AtomicU64,
+}
+
+impl WorkerCounters {
+    pub(crate) fn add_busy(&self, elapsed: Duration) {
+        self.busy_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.jobs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_idle(&self, elapsed: Duration) {
+        self.idle_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct Histogram {
+    // 最后一个桶是 +Inf
+    counts: [AtomicU64; BUCKETS.len() + 1],
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn record(&self, value: Duration) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(value.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            bounds: BUCKETS.to_vec(),
+            counts: self
+                .counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            sum: Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Metrics {
+    /// Worker id 的计数器，Worker 启动时取一次
+    pub(crate) fn worker(&self, id: usize) -> Arc<WorkerCounters> {
+        let mut workers = self.workers.lock().unwrap_or_else(PoisonError::into_inner);
+        while workers.len() <= id {
+            workers.push(Arc::default());
+        }
+        Arc::clone(&workers[id])
+    }
+
+    pub(crate) fn record_job(&self, wait: Duration, run: Duration) {
+        self.wait_time.record(wait);
+        self.run_time.record(run);
+    }
+}
+
+/// 直方图快照，counts 比 bounds 多一个元素，最后一个是超过所有上界的数量
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    pub bounds: Vec<Duration>,
+    pub counts: Vec<u64>,
+    pub sum: Duration,
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// 单个 Worker 的累计时间，只统计到它上一次取任务或执行完任务为止
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerStats {
+    pub id: usize,
+    pub busy: Duration,
+    pub idle: Duration,
+    pub jobs: u64,
+}
+
+/// ThreadPool::stats 返回的快照
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// 正在排队的任务数
+    pub queue_depth: usize,
+    /// 当前在岗的 Worker 数
+    pub live_workers: usize,
+    pub submitted: u64,
+    /// 执行完的任务数，包括 panic 的任务
+    pub completed: u64,
+    pub panicked: u64,
+    /// 有界队列放不下而被拒绝的任务数，包括到期时放不下的定时任务
+    pub rejected: u64,
+    /// 按 Worker id 排列，包括已经退休的 id
+    pub workers: Vec<WorkerStats>,
+    /// 从提交到开始执行的时间
+    pub wait_time: HistogramSnapshot,
+    /// 任务执行的时间
+    pub run_time: HistogramSnapshot,
+}
+
+impl PoolStats {
+    /// 转成 Prometheus 文本格式
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            let _ = writeln!(out, "{name} {value}");
+        };
+        metric(
+            "thread_pool_queue_depth",
+            "gauge",
+            "Jobs waiting in the queue.",
+            self.queue_depth as u64,
+        );
+        metric(
+            "thread_pool_live_workers",
+            "gauge",
+            "Worker threads currently running.",
+            self.live_workers as u64,
+        );
+        metric(
+            "thread_pool_jobs_submitted_total",
+            "counter",
+            "Jobs accepted by the pool.",
+            self.submitted,
+        );
+        metric(
+            "thread_pool_jobs_completed_total",
+            "counter",
+            "Jobs that finished running, including panicked ones.",
+            self.completed,
+        );
+        metric(
+            "thread_pool_jobs_panicked_total",
+            "counter",
+            "Jobs that panicked.",
+            self.panicked,
+        );
+        metric(
+            "thread_pool_jobs_rejected_total",
+            "counter",
+            "Jobs rejected because the bounded queue was full.",
+            self.rejected,
+        );
+
+        write_worker_times(
+            &mut out,
+            "thread_pool_worker_busy_seconds_total",
+            "Time each worker spent running jobs.",
+            self.workers.iter().map(|worker| (worker.id, worker.busy)),
+        );
+        write_worker_times(
+            &mut out,
+            "thread_pool_worker_idle_seconds_total",
+            "Time each worker spent waiting for jobs.",
+            self.workers.iter().map(|worker| (worker.id, worker.idle)),
+        );
+        write_histogram(
+            &mut out,
+            "thread_pool_job_wait_seconds",
+            "Time from submission until a worker started the job.",
+            &self.wait_time,
+        );
+        write_histogram(
+            &mut out,
+            "thread_pool_job_run_seconds",
+            "Time spent running each job.",
+            &self.run_time,
+        );
+        out
+    }
+}
+
+fn write_worker_times(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    times: impl Iterator<Item = (usize, Duration)>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for (id, time) in times {
+        let _ = writeln!(out, "{name}{{worker=\"{id}\"}} {}", time.as_secs_f64());
+    }
+}
+
+fn write_histogram(out: &mut String, name: &str, help: &str, histogram: &HistogramSnapshot) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} histogram");
+    // Prometheus 的桶是累计的
+    let mut cumulative = 0;
+    for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+        cumulative += count;
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{le=\"{}\"}} {cumulative}",
+            bound.as_secs_f64()
+        );
+    }
+    let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", histogram.count());
+    let _ = writeln!(out, "{name}_sum {}", histogram.sum.as_secs_f64());
+    let _ = writeln!(out, "{name}_count {}", histogram.count());
+}
+
+/// 定期导出的指标写到哪里
+pub enum StatsSink {
+    /// 每次整个覆盖文件，可以交给 node_exporter 的 textfile collector
+    File(PathBuf),
+    Callback(Box<dyn Fn(&str) + Send + Sync + 'static>),
+}
+
+impl StatsSink {
+    fn write(&self, text: &str) -> io::Result<()> {
+        match self {
+            StatsSink::File(path) => {
+                // 先写临时文件再改名，读的一方不会看到写了一半的内容
+                let tmp = path.with_extension("tmp");
+                fs::write(&tmp, text)?;
+                fs::rename(&tmp, path)
+            }
+            StatsSink::Callback(callback) => {
+                callback(text);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Shared {
+    pub(crate) fn stats(&self) -> PoolStats {
+        let metrics = &self.metrics;
+        let workers = metrics
+            .workers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .enumerate()
+            .map(|(id, counters)| WorkerStats {
+                id,
+                busy: Duration::from_nanos(counters.busy_nanos.load(Ordering::Relaxed)),
+                idle: Duration::from_nanos(counters.idle_nanos.load(Ordering::Relaxed)),
+                jobs: counters.jobs.load(Ordering::Relaxed),
+            })
+            .collect();
+        PoolStats {
+            queue_depth: self.queue_depth(),
+            live_workers: self.sizing.live(),
+            submitted: metrics.submitted.load(Ordering::SeqCst),
+            completed: metrics.completed.load(Ordering::SeqCst),
+            panicked: metrics.panicked.load(Ordering::SeqCst),
+            rejected: metrics.rejected.load(Ordering::SeqCst),
+            workers,
+            wait_time: metrics.wait_time.snapshot(),
+            run_time: metrics.run_time.snapshot(),
+        }
+    }
+}
+
+impl ThreadPool {
+    /// 每隔 period 把 stats 以 Prometheus 文本格式写到 sink，返回的句柄可以停止导出
+    ///
+    /// 导出在线程池的 Worker 上执行；写文件失败时发出 PoolEvent::StatsExportFailed，下一次继续。
+    pub fn report_stats_every(
+        &self,
+        period: Duration,
+        sink: StatsSink,
+    ) -> io::Result<super::ScheduleHandle> {
+        let shared: Weak<Shared> = Arc::downgrade(&self.shared);
+        self.schedule_every(period, move || {
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+            if let Err(err) = sink.write(&shared.stats().to_prometheus()) {
+                shared.emit(PoolEvent::StatsExportFailed {
+                    error: err.to_string(),
+                });
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn counts_jobs() {
+        let pool = ThreadPool::with_panic_handler(2, |_| {});
+        for i in 0..10 {
+            pool.execute(move || {
+                if i % 5 == 0 {
+                    panic!("job {i} failed");
+                }
+            })
+            .unwrap();
+        }
+        assert_eq!(pool.submit(|| 1).join(), Ok(1));
+        // submit 的结果在任务计数之前就送达了，稍等计数更新
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while pool.stats().workers.iter().map(|w| w.jobs).sum::<u64>() < 11 {
+            assert!(std::time::Instant::now() < deadline);
+            thread::yield_now();
+        }
+
+        let stats = pool.stats();
+        assert_eq!(stats.submitted, 11);
+        assert_eq!(stats.completed, 11);
+        assert_eq!(stats.panicked, 2);
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.live_workers, 2);
+        assert_eq!(stats.run_time.count(), 11);
+        assert_eq!(stats.wait_time.count(), 11);
+        assert_eq!(stats.workers.iter().map(|w| w.jobs).sum::<u64>(), 11);
+    }
+
+    #[test]
+    fn tracks_busy_time_and_queue_depth() {
+        let pool = ThreadPool::new(1);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(20));
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+        started_rx.recv().unwrap();
+        for _ in 0..3 {
+            pool.execute(|| {}).unwrap();
+        }
+        assert_eq!(pool.stats().queue_depth, 3);
+        release_tx.send(()).unwrap();
+        drop(pool.submit(|| ()).join());
+
+        let stats = pool.stats();
+        assert!(stats.workers[0].busy >= Duration::from_millis(20));
+        // 排队的三个任务至少等了 20ms，都落在 10ms 以上的桶里；机器再慢也只会等得更久
+        assert!(stats.wait_time.counts[4..].iter().sum::<u64>() >= 3);
+        assert!(stats.wait_time.sum >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn prometheus_format() {
+        let stats = PoolStats {
+            queue_depth: 2,
+            live_workers: 1,
+            submitted: 5,
+            completed: 3,
+            panicked: 1,
+            rejected: 2,
+            workers: vec![WorkerStats {
+                id: 0,
+                busy: Duration::from_millis(1500),
+                idle: Duration::from_millis(250),
+                jobs: 3,
+            }],
+            wait_time: HistogramSnapshot {
+                bounds: BUCKETS.to_vec(),
+                counts: vec![1, 2, 0, 0, 0, 0, 0, 1],
+                sum: Duration::from_millis(20),
+            },
+            run_time: Histogram::default().snapshot(),
+        };
+        let text = stats.to_prometheus();
+        for line in [
+            "# TYPE thread_pool_queue_depth gauge",
+            "thread_pool_queue_depth 2",
+            "thread_pool_jobs_submitted_total 5",
+            "thread_pool_jobs_panicked_total 1",
+            "thread_pool_jobs_rejected_total 2",
+            "thread_pool_worker_busy_seconds_total{worker=\"0\"} 1.5",
+            "thread_pool_worker_idle_seconds_total{worker=\"0\"} 0.25",
+            "# TYPE thread_pool_job_wait_seconds histogram",
+            "thread_pool_job_wait_seconds_bucket{le=\"0.00001\"} 1",
+            "thread_pool_job_wait_seconds_bucket{le=\"0.0001\"} 3",
+            "thread_pool_job_wait_seconds_bucket{le=\"10\"} 3",
+            "thread_pool_job_wait_seconds_bucket{le=\"+Inf\"} 4",
+            "thread_pool_job_wait_seconds_sum 0.02",
+            "thread_pool_job_wait_seconds_count 4",
+            "thread_pool_job_run_seconds_count 0",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {line:?} in\n{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn periodic_reporter_writes_to_callback_and_file() {
+        let pool = ThreadPool::new(2);
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let handle = pool
+            .report_stats_every(
+                Duration::from_millis(10),
+                StatsSink::Callback(Box::new(move |text| {
+                    let _ = tx.lock().unwrap().send(text.to_string());
+                })),
+            )
+            .unwrap();
+        let text = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(text.contains("thread_pool_live_workers 2"));
+        handle.cancel();
+
+        let path = std::env::temp_dir().join(format!("pool-stats-{}.prom", std::process::id()));
+        let handle = pool
+            .report_stats_every(Duration::from_millis(10), StatsSink::File(path.clone()))
+            .unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !path.exists() {
+            assert!(std::time::Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(5));
+        }
+        handle.cancel();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("# TYPE thread_pool_jobs_completed_total counter"));
+        let _ = fs::remove_file(&path);
+    }
+}
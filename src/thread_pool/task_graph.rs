@@ -0,0 +1,496 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use super::job_handle::panic_message;
+use super::{Scope, ThreadPool};
+
+type NodeFn<'a, T, E> = Box<dyn FnOnce(Vec<T>) -> Result<T, E> + Send + 'a>;
+
+/// TaskGraph 里的节点编号，由 add_node 返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+/// 构建或者校验任务图失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// 节点编号不属于这个图
+    UnknownNode(NodeId),
+    /// 图里有环，按依赖方向列出环上节点的名字
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::UnknownNode(id) => write!(f, "node {} is not in this graph", id.0),
+            GraphError::Cycle(names) => write!(f, "dependency cycle: {}", names.join(" -> ")),
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+/// 单个节点的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeOutcome<T, E> {
+    Done(T),
+    Failed(E),
+    Panicked(String),
+    /// 上游的 cause 失败了，这个节点没有执行
+    Skipped {
+        cause: NodeId,
+    },
+}
+
+/// 执行记录里的一条，时间都从 run 开始算起
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub node: NodeId,
+    pub name: String,
+    pub started: Duration,
+    pub finished: Duration,
+}
+
+/// TaskGraph::run 的结果
+#[derive(Debug)]
+pub struct GraphReport<T, E> {
+    /// 按 NodeId 排列的每个节点的结果
+    pub outcomes: Vec<NodeOutcome<T, E>>,
+    /// 执行过的节点，按开始执行的顺序排列；节点在所有前驱结束后才开始，所以这也是一个拓扑顺序
+    pub trace: Vec<TraceEntry>,
+}
+
+impl<T, E> GraphReport<T, E> {
+    pub fn outcome(&self, node: NodeId) -> &NodeOutcome<T, E> {
+        &self.outcomes[node.0]
+    }
+
+    /// 所有节点都执行成功
+    pub fn is_success(&self) -> bool {
+        self.outcomes
+            .iter()
+            .all(|outcome| matches!(outcome, NodeOutcome::Done(_)))
+    }
+}
+
+struct Node<'a, T, E> {
+    name: String,
+    run: NodeFn<'a, T, E>,
+    // 按添加边的顺序，节点收到的输入也是这个顺序
+    predecessors: Vec<NodeId>,
+    successors: Vec<NodeId>,
+}
+
+/// 有依赖关系的任务图，在 ThreadPool 上执行
+///
+/// 节点是一个闭包，参数是所有前驱的输出，返回自己的输出或者错误。
+/// 一个节点的所有前驱都成功后它才会被提交到线程池；前驱失败时它和它的下游都被跳过。
+/// 节点在 pool.scope 里执行，闭包可以借用 'a 的数据。
+pub struct TaskGraph<'a, T, E> {
+    nodes: Vec<Node<'a, T, E>>,
+}
+
+impl<'a, T, E> TaskGraph<'a, T, E>
+where
+    T: Clone + Send + 'a,
+    E: Send + 'a,
+{
+    pub fn new() -> TaskGraph<'a, T, E> {
+        TaskGraph { nodes: Vec::new() }
+    }
+
+    pub fn add_node<F>(&mut self, name: impl Into<String>, f: F) -> NodeId
+    where
+        F: FnOnce(Vec<T>) -> Result<T, E> + Send + 'a,
+    {
+        self.nodes.push(Node {
+            name: name.into(),
+            run: Box::new(f),
+            predecessors: Vec::new(),
+            successors: Vec::new(),
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// to 依赖 from：from 的输出会作为 to 的输入之一
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId) -> Result<(), GraphError> {
+        for id in [from, to] {
+            if id.0 >= self.nodes.len() {
+                return Err(GraphError::UnknownNode(id));
+            }
+        }
+        self.nodes[from.0].successors.push(to);
+        self.nodes[to.0].predecessors.push(from);
+        Ok(())
+    }
+
+    /// 检查有没有环，没有环时返回一个拓扑顺序
+    pub fn validate(&self) -> Result<Vec<NodeId>, GraphError> {
+        let mut remaining: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|node| node.predecessors.len())
+            .collect();
+        let mut ready: Vec<usize> = (0..self.nodes.len())
+            .filter(|&id| remaining[id] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(id) = ready.pop() {
+            order.push(NodeId(id));
+            for next in &self.nodes[id].successors {
+                remaining[next.0] -= 1;
+                if remaining[next.0] == 0 {
+                    ready.push(next.0);
+                }
+            }
+        }
+        if order.len() == self.nodes.len() {
+            return Ok(order);
+        }
+
+        // 剩下的节点都至少有一个剩下的前驱，沿着前驱一直往回走，一定会走回到走过的节点
+        let mut path = vec![(0..self.nodes.len()).find(|&id| remaining[id] > 0).unwrap()];
+        let mut seen = HashSet::from([path[0]]);
+        loop {
+            let current = *path.last().unwrap();
+            let previous = self.nodes[current]
+                .predecessors
+                .iter()
+                .map(|id| id.0)
+                .find(|&id| remaining[id] > 0)
+                .unwrap();
+            if !seen.insert(previous) {
+                let start = path.iter().position(|&id| id == previous).unwrap();
+                // path 是逆着依赖方向走的，反过来再从 previous 开始
+                let cycle = std::iter::once(previous)
+                    .chain(path[start..].iter().rev().copied())
+                    .map(|id| self.nodes[id].name.clone())
+                    .collect();
+                return Err(GraphError::Cycle(cycle));
+            }
+            path.push(previous);
+        }
+    }
+
+    /// 校验之后在 pool 上执行整个图，等所有节点结束或被跳过后返回
+    pub fn run(self, pool: &ThreadPool) -> Result<GraphReport<T, E>, GraphError> {
+        self.validate()?;
+        let n = self.nodes.len();
+        let mut runs = Vec::with_capacity(n);
+        let mut graph = Vec::with_capacity(n);
+        for node in self.nodes {
+            runs.push(Mutex::new(Some(node.run)));
+            graph.push((node.name, node.predecessors, node.successors));
+        }
+        let state = Mutex::new(RunState {
+            remaining: graph.iter().map(|(_, preds, _)| preds.len()).collect(),
+            outcomes: (0..n).map(|_| None).collect(),
+            trace: Vec::new(),
+        });
+        let run = GraphRun {
+            graph: &graph,
+            runs: &runs,
+            state: &state,
+            start: Instant::now(),
+        };
+
+        pool.scope(|s| {
+            let ready: Vec<usize> = (0..n).filter(|&id| graph[id].1.is_empty()).collect();
+            for id in ready {
+                run.start_node(s, id);
+            }
+        });
+
+        let state = state.into_inner().unwrap_or_else(PoisonError::into_inner);
+        let mut trace = state.trace;
+        trace.sort_by_key(|entry| entry.started);
+        Ok(GraphReport {
+            outcomes: state.outcomes.into_iter().map(Option::unwrap).collect(),
+            trace,
+        })
+    }
+}
+
+impl<'a, T, E> Default for TaskGraph<'a, T, E>
+where
+    T: Clone + Send + 'a,
+    E: Send + 'a,
+{
+    fn default() -> Self {
+        TaskGraph::new()
+    }
+}
+
+struct RunState<T, E> {
+    // 每个节点还有几个前驱没结束
+    remaining: Vec<usize>,
+    outcomes: Vec<Option<NodeOutcome<T, E>>>,
+    trace: Vec<TraceEntry>,
+}
+
+type Edges = (String, Vec<NodeId>, Vec<NodeId>);
+
+struct GraphRun<'r, 'a, T, E> {
+    graph: &'r [Edges],
+    runs: &'r [Mutex<Option<NodeFn<'a, T, E>>>],
+    state: &'r Mutex<RunState<T, E>>,
+    start: Instant,
+}
+
+impl<T, E> Clone for GraphRun<'_, '_, T, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, E> Copy for GraphRun<'_, '_, T, E> {}
+
+impl<'r, 'a, T, E> GraphRun<'r, 'a, T, E>
+where
+    T: Clone + Send + 'a,
+    E: Send + 'a,
+{
+    /// 所有前驱都成功了：收集它们的输出，把节点交给线程池
+    fn start_node<'scope>(self, s: &'scope Scope<'scope, '_>, id: usize)
+    where
+        'r: 'scope,
+    {
+        let inputs: Vec<T> = {
+            let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            self.graph[id]
+                .1
+                .iter()
+                .map(|pred| match &state.outcomes[pred.0] {
+                    Some(NodeOutcome::Done(output)) => output.clone(),
+                    _ => unreachable!("node started before its predecessors succeeded"),
+                })
+                .collect()
+        };
+        let f = self.runs[id]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .unwrap();
+        s.spawn(move || {
+            let started = self.start.elapsed();
+            let outcome = match panic::catch_unwind(AssertUnwindSafe(|| f(inputs))) {
+                Ok(Ok(output)) => NodeOutcome::Done(output),
+                Ok(Err(err)) => NodeOutcome::Failed(err),
+                Err(payload) => NodeOutcome::Panicked(panic_message(payload.as_ref())),
+            };
+            let finished = self.start.elapsed();
+            let succeeded = matches!(outcome, NodeOutcome::Done(_));
+
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.trace.push(TraceEntry {
+                node: NodeId(id),
+                name: self.graph[id].0.clone(),
+                started,
+                finished,
+            });
+            state.outcomes[id] = Some(outcome);
+            let mut ready = Vec::new();
+            if succeeded {
+                for next in &self.graph[id].2 {
+                    state.remaining[next.0] -= 1;
+                    // 另一个前驱已经失败时，这个节点已经被标记为跳过
+                    if state.remaining[next.0] == 0 && state.outcomes[next.0].is_none() {
+                        ready.push(next.0);
+                    }
+                }
+            } else {
+                self.skip_successors(&mut state, id, NodeId(id));
+            }
+            drop(state);
+            for next in ready {
+                self.start_node(s, next);
+            }
+        });
+    }
+
+    /// 把 id 的所有下游标记为跳过，cause 是最早失败的节点
+    fn skip_successors(&self, state: &mut RunState<T, E>, id: usize, cause: NodeId) {
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            for next in &self.graph[current].2 {
+                if state.outcomes[next.0].is_none() {
+                    state.outcomes[next.0] = Some(NodeOutcome::Skipped { cause });
+                    stack.push(next.0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn position(report: &GraphReport<i64, String>, node: NodeId) -> usize {
+        report
+            .trace
+            .iter()
+            .position(|entry| entry.node == node)
+            .unwrap()
+    }
+
+    #[test]
+    fn diamond_passes_outputs_along_edges() {
+        let pool = ThreadPool::new(4);
+        let mut graph: TaskGraph<i64, String> = TaskGraph::new();
+        let source = graph.add_node("source", |_| Ok(10));
+        let double = graph.add_node("double", |inputs| Ok(inputs[0] * 2));
+        let square = graph.add_node("square", |inputs| Ok(inputs[0] * inputs[0]));
+        let sum = graph.add_node("sum", |inputs| Ok(inputs.iter().sum()));
+        graph.add_edge(source, double).unwrap();
+        graph.add_edge(source, square).unwrap();
+        graph.add_edge(double, sum).unwrap();
+        graph.add_edge(square, sum).unwrap();
+
+        let report = graph.run(&pool).unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.outcome(sum), &NodeOutcome::Done(120));
+        assert_eq!(report.trace.len(), 4);
+        assert!(position(&report, source) < position(&report, double));
+        assert!(position(&report, square) < position(&report, sum));
+        for entry in &report.trace {
+            assert!(entry.started <= entry.finished);
+        }
+    }
+
+    #[test]
+    fn inputs_follow_edge_order() {
+        let pool = ThreadPool::new(2);
+        let mut graph: TaskGraph<String, ()> = TaskGraph::new();
+        let a = graph.add_node("a", |_| Ok(String::from("a")));
+        let b = graph.add_node("b", |_| Ok(String::from("b")));
+        let joined = graph.add_node("join", |inputs| Ok(inputs.concat()));
+        graph.add_edge(b, joined).unwrap();
+        graph.add_edge(a, joined).unwrap();
+        let report = graph.run(&pool).unwrap();
+        assert_eq!(
+            report.outcome(joined),
+            &NodeOutcome::Done(String::from("ba"))
+        );
+    }
+
+    #[test]
+    fn independent_nodes_run_in_parallel() {
+        let pool = ThreadPool::new(4);
+        let mut graph: TaskGraph<(), ()> = TaskGraph::new();
+        let barrier = std::sync::Barrier::new(3);
+        for i in 0..3 {
+            let barrier = &barrier;
+            graph.add_node(format!("leaf {i}"), move |_| {
+                // 三个节点必须同时执行才能通过
+                barrier.wait();
+                Ok(())
+            });
+        }
+        assert!(graph.run(&pool).unwrap().is_success());
+    }
+
+    #[test]
+    fn failure_skips_downstream_only() {
+        let pool = ThreadPool::new(2);
+        let mut graph: TaskGraph<i64, String> = TaskGraph::new();
+        let fetch = graph.add_node("fetch", |_| Err(String::from("network down")));
+        let parse = graph.add_node("parse", |inputs| Ok(inputs[0]));
+        let render = graph.add_node("render", |inputs| Ok(inputs[0]));
+        let config = graph.add_node("config", |_| Ok(1));
+        let boom = graph.add_node("boom", |_| panic!("bad config"));
+        let after_boom = graph.add_node("after boom", |_| Ok(0));
+        graph.add_edge(fetch, parse).unwrap();
+        graph.add_edge(parse, render).unwrap();
+        graph.add_edge(config, render).unwrap();
+        graph.add_edge(config, boom).unwrap();
+        graph.add_edge(boom, after_boom).unwrap();
+
+        let report = graph.run(&pool).unwrap();
+        assert!(!report.is_success());
+        assert_eq!(
+            report.outcome(fetch),
+            &NodeOutcome::Failed(String::from("network down"))
+        );
+        assert_eq!(
+            report.outcome(parse),
+            &NodeOutcome::Skipped { cause: fetch }
+        );
+        assert_eq!(
+            report.outcome(render),
+            &NodeOutcome::Skipped { cause: fetch }
+        );
+        assert_eq!(report.outcome(config), &NodeOutcome::Done(1));
+        assert_eq!(
+            report.outcome(boom),
+            &NodeOutcome::Panicked(String::from("bad config"))
+        );
+        assert_eq!(
+            report.outcome(after_boom),
+            &NodeOutcome::Skipped { cause: boom }
+        );
+        let ran: HashSet<_> = report.trace.iter().map(|entry| entry.node).collect();
+        assert_eq!(ran, HashSet::from([fetch, config, boom]));
+    }
+
+    #[test]
+    fn cycles_are_rejected_before_running() {
+        let pool = ThreadPool::new(1);
+        let mut graph: TaskGraph<(), ()> = TaskGraph::new();
+        let start = graph.add_node("start", |_| panic!("must not run"));
+        let a = graph.add_node("a", |_| Ok(()));
+        let b = graph.add_node("b", |_| Ok(()));
+        let c = graph.add_node("c", |_| Ok(()));
+        graph.add_edge(start, a).unwrap();
+        graph.add_edge(a, b).unwrap();
+        graph.add_edge(b, c).unwrap();
+        graph.add_edge(c, a).unwrap();
+
+        let Err(GraphError::Cycle(cycle)) = graph.validate() else {
+            panic!("cycle not detected");
+        };
+        // 环从哪个节点开始不固定，但一定首尾相同、依次相连
+        assert_eq!(cycle.len(), 4);
+        assert_eq!(cycle.first(), cycle.last());
+        let text = cycle.join(" -> ");
+        assert!(
+            ["a -> b -> c -> a", "b -> c -> a -> b", "c -> a -> b -> c"].contains(&text.as_str())
+        );
+        assert!(matches!(graph.run(&pool), Err(GraphError::Cycle(_))));
+    }
+
+    #[test]
+    fn unknown_nodes_are_rejected() {
+        let mut other: TaskGraph<(), ()> = TaskGraph::new();
+        other.add_node("x", |_| Ok(()));
+        let foreign = other.add_node("y", |_| Ok(()));
+        let mut graph: TaskGraph<(), ()> = TaskGraph::new();
+        let only = graph.add_node("only", |_| Ok(()));
+        assert_eq!(
+            graph.add_edge(only, foreign),
+            Err(GraphError::UnknownNode(foreign))
+        );
+    }
+
+    #[test]
+    fn nodes_borrow_local_data() {
+        let pool = ThreadPool::new(2);
+        let words = ["build", "test", "deploy"];
+        let mut graph: TaskGraph<usize, ()> = TaskGraph::new();
+        let count = graph.add_node("count", |_| Ok(words.len()));
+        let longest = graph.add_node("longest", |_| {
+            thread::yield_now();
+            Ok(words.iter().map(|w| w.len()).max().unwrap())
+        });
+        let total = graph.add_node("total", |inputs| Ok(inputs[0] * inputs[1]));
+        graph.add_edge(count, total).unwrap();
+        graph.add_edge(longest, total).unwrap();
+        let report = graph.run(&pool).unwrap();
+        assert_eq!(report.outcome(total), &NodeOutcome::Done(18));
+    }
+}
@@ -0,0 +1,517 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, Weak};
+use std::task::Waker;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{ExecuteError, Priority, Shared};
+
+type Repeat = Arc<dyn Fn() + Send + Sync + 'static>;
+
+/// 周期任务下一次的时间怎么算
+#[derive(Clone, Copy)]
+enum Cadence {
+    /// 上一次的计划时间加上周期，慢了会连着追赶，但不会重叠执行
+    FixedRate(Duration),
+    /// 上一次执行结束的时间加上间隔
+    FixedDelay(Duration),
+}
+
+impl Cadence {
+    fn next(self, deadline: Instant) -> Instant {
+        match self {
+            Cadence::FixedRate(period) => deadline + period,
+            Cadence::FixedDelay(delay) => Instant::now() + delay,
+        }
+    }
+}
+
+enum Task {
+    Once(Box<dyn FnOnce() + Send + 'static>),
+    Repeating(Repeat, Cadence),
+    /// 到期时在定时器线程上直接唤醒 future，不占用 Worker，也不受有界队列限制
+    Wake(Waker),
+}
+
+struct Entry {
+    task: Task,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// 定时器线程：按到期时间把任务交给线程池
+///
+/// 定时器线程从不执行用户的任务，也不会因为有界队列满了而阻塞：放不下的任务直接算作被拒绝，
+/// 否则一个任务就能拖慢或者弄死所有定时任务。
+///
+/// 到期时间放在小顶堆里，任务本身放在 HashMap 里，取消时只需从 HashMap 删除。
+pub(crate) struct Timer {
+    state: Mutex<TimerState>,
+    wakeup: Condvar,
+}
+
+#[derive(Default)]
+struct TimerState {
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    entries: HashMap<u64, Entry>,
+    next_id: u64,
+    thread: Option<thread::JoinHandle<()>>,
+    shutdown: bool,
+}
+
+/// schedule_* 返回的句柄，用来取消还没执行的定时任务
+///
+/// 丢弃句柄不会取消任务。
+pub struct ScheduleHandle {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    timer: Weak<Timer>,
+}
+
+impl ScheduleHandle {
+    /// 取消任务：一次性任务如果还没交给线程池就不会执行，周期任务不会再有下一次
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(timer) = self.timer.upgrade() {
+            timer.lock().entries.remove(&self.id);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Timer {
+    pub(crate) fn new() -> Timer {
+        Timer {
+            state: Mutex::new(TimerState::default()),
+            wakeup: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TimerState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 没有等待中的定时任务
+    #[cfg(test)]
+    pub(crate) fn is_idle(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    fn insert(&self, state: &mut TimerState, id: u64, deadline: Instant, entry: Entry) {
+        state.deadlines.push(Reverse((deadline, id)));
+        state.entries.insert(id, entry);
+        self.wakeup.notify_one();
+    }
+
+    /// 周期任务执行完之后重新排期，期间被取消或者线程池已关闭时放弃
+    fn reschedule(&self, id: u64, deadline: Instant, entry: Entry) {
+        let mut state = self.lock();
+        if !state.shutdown && !entry.cancelled.load(Ordering::SeqCst) {
+            self.insert(&mut state, id, deadline, entry);
+        }
+    }
+
+    /// 停止定时器线程，还没到期的任务全部丢弃
+    pub(crate) fn shutdown(&self) {
+        let thread = {
+            let mut state = self.lock();
+            state.shutdown = true;
+            state.entries.clear();
+            self.wakeup.notify_all();
+            state.thread.take()
+        };
+        if let Some(thread) = thread {
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn run(self: &Arc<Self>, shared: &Arc<Shared>) {
+        let mut state = self.lock();
+        loop {
+            if state.shutdown {
+                return;
+            }
+            let Some(&Reverse((deadline, id))) = state.deadlines.peek() else {
+                state = self
+                    .wakeup
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+                continue;
+            };
+            let now = Instant::now();
+            if deadline > now {
+                state = self
+                    .wakeup
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+                continue;
+            }
+            state.deadlines.pop();
+            // 已经取消的任务只剩下堆里的到期时间
+            let Some(entry) = state.entries.remove(&id) else {
+                continue;
+            };
+            drop(state);
+            self.fire(shared, id, deadline, entry);
+            state = self.lock();
+        }
+    }
+
+    fn fire(self: &Arc<Self>, shared: &Arc<Shared>, id: u64, deadline: Instant, entry: Entry) {
+        let Entry { task, cancelled } = entry;
+        match task {
+            Task::Once(f) => {
+                let _ = shared.offer(Priority::Normal, f);
+            }
+            Task::Wake(waker) => waker.wake(),
+            Task::Repeating(f, cadence) => {
+                let timer = Arc::clone(self);
+                let (repeat, flag) = (Arc::clone(&f), Arc::clone(&cancelled));
+                let job = Box::new(move || {
+                    if flag.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    // f panic 时不会走到下面的重新排期，周期任务就此停止
+                    repeat();
+                    let entry = Entry {
+                        task: Task::Repeating(repeat, cadence),
+                        cancelled: flag,
+                    };
+                    timer.reschedule(id, cadence.next(deadline), entry);
+                });
+                if shared.offer(Priority::Normal, job).is_err() {
+                    // 有界队列拒绝了这一次，跳过它，按周期等下一次
+                    self.reschedule(
+                        id,
+                        cadence.next(deadline),
+                        Entry {
+                            task: Task::Repeating(f, cadence),
+                            cancelled,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// 定时器线程提交到期的任务：不阻塞，也不在当前线程上执行，放不下时丢弃并计入 rejected
+    fn offer(
+        self: &Arc<Self>,
+        priority: Priority,
+        run: Box<dyn FnOnce() + Send + 'static>,
+    ) -> Result<(), ExecuteError> {
+        let job = self.new_job(run);
+        if let Some(deterministic) = &self.deterministic {
+            self.metrics.submitted.fetch_add(1, Ordering::SeqCst);
+            deterministic.push(job, priority);
+            return Ok(());
+        }
+        match self.scheduler.try_push(job, priority) {
+            Ok(()) => {
+                self.metrics.submitted.fetch_add(1, Ordering::SeqCst);
+                self.maybe_grow();
+                Ok(())
+            }
+            Err(_) => {
+                self.metrics.rejected.fetch_add(1, Ordering::SeqCst);
+                Err(ExecuteError::QueueFull)
+            }
+        }
+    }
+
+    /// 定时器线程启动失败时返回错误，下一次调用会再试
+    fn schedule(self: &Arc<Self>, deadline: Instant, task: Task) -> io::Result<ScheduleHandle> {
+        let timer = &self.timer;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut state = timer.lock();
+        if state.shutdown {
+            cancelled.store(true, Ordering::SeqCst);
+        }
+        // 第一次使用时才启动定时器线程
+        if state.thread.is_none() && !state.shutdown {
+            let mut builder = thread::Builder::new();
+            if let Some(prefix) = &self.config.thread_name {
+                builder = builder.name(format!("{prefix}-timer"));
+            }
+            let shared = Arc::clone(self);
+            let thread = builder.spawn(move || shared.timer.run(&shared))?;
+            state.thread = Some(thread);
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        if !state.shutdown {
+            let entry = Entry {
+                task,
+                cancelled: Arc::clone(&cancelled),
+            };
+            timer.insert(&mut state, id, deadline, entry);
+        }
+        Ok(ScheduleHandle {
+            id,
+            cancelled,
+            timer: Arc::downgrade(timer),
+        })
+    }
+
+    pub(crate) fn schedule_once(
+        self: &Arc<Self>,
+        deadline: Instant,
+        f: Box<dyn FnOnce() + Send + 'static>,
+    ) -> io::Result<ScheduleHandle> {
+        self.schedule(deadline, Task::Once(f))
+    }
+
+    pub(crate) fn schedule_wake(
+        self: &Arc<Self>,
+        deadline: Instant,
+        waker: Waker,
+    ) -> io::Result<ScheduleHandle> {
+        self.schedule(deadline, Task::Wake(waker))
+    }
+
+    pub(crate) fn schedule_fixed_rate(
+        self: &Arc<Self>,
+        period: Duration,
+        f: Repeat,
+    ) -> io::Result<ScheduleHandle> {
+        self.schedule(
+            Instant::now() + period,
+            Task::Repeating(f, Cadence::FixedRate(period)),
+        )
+    }
+
+    pub(crate) fn schedule_fixed_delay(
+        self: &Arc<Self>,
+        delay: Duration,
+        f: Repeat,
+    ) -> io::Result<ScheduleHandle> {
+        self.schedule(
+            Instant::now() + delay,
+            Task::Repeating(f, Cadence::FixedDelay(delay)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_pool::{OverflowPolicy, ScheduleHandle};
+    use crate::ThreadPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn schedule_after_waits_for_delay() {
+        let pool = ThreadPool::new(2);
+        let (tx, rx) = mpsc::channel();
+        let start = Instant::now();
+        pool.schedule_after(Duration::from_millis(50), move || {
+            tx.send(Instant::now()).unwrap()
+        })
+        .unwrap();
+        let fired = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(fired - start >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn schedule_at_runs_in_deadline_order() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        let now = Instant::now();
+        for (name, ms) in [("c", 60), ("a", 20), ("b", 40)] {
+            let tx = tx.clone();
+            pool.schedule_at(now + Duration::from_millis(ms), move || {
+                tx.send(name).unwrap()
+            })
+            .unwrap();
+        }
+        let order: Vec<_> = (0..3)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(order, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn cancelled_job_never_runs() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel::<()>();
+        let handle = pool
+            .schedule_after(Duration::from_millis(30), move || tx.send(()).unwrap())
+            .unwrap();
+        handle.cancel();
+        assert!(handle.is_cancelled());
+        // 发送端随任务一起被丢弃
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(1)),
+            Err(mpsc::RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn schedule_every_repeats_until_cancelled() {
+        let pool = ThreadPool::new(2);
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&count);
+        let handle = pool
+            .schedule_every(Duration::from_millis(10), move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        while count.load(Ordering::SeqCst) < 3 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        handle.cancel();
+        thread::sleep(Duration::from_millis(30));
+        let after_cancel = count.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(count.load(Ordering::SeqCst), after_cancel);
+    }
+
+    // 每次执行耗时 20ms，返回前 5 次开始执行的时间，从提交时算起
+    fn start_times(
+        schedule: impl FnOnce(&ThreadPool, Box<dyn Fn() + Send + Sync>) -> ScheduleHandle,
+    ) -> Vec<Duration> {
+        let pool = ThreadPool::new(2);
+        let submitted = Instant::now();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let recorded = Arc::clone(&starts);
+        let handle = schedule(
+            &pool,
+            Box::new(move || {
+                let mut starts = recorded.lock().unwrap();
+                starts.push(Instant::now());
+                if starts.len() == 5 {
+                    tx.lock().unwrap().send(()).unwrap();
+                }
+                drop(starts);
+                thread::sleep(Duration::from_millis(20));
+            }),
+        );
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        handle.cancel();
+        let starts = starts.lock().unwrap();
+        starts[..5].iter().map(|start| *start - submitted).collect()
+    }
+
+    #[test]
+    fn fixed_rate_keeps_to_the_schedule() {
+        // 周期 40ms：第 n 次（从 1 数）在提交后 n * 40ms 开始，执行耗时不会累积
+        let starts =
+            start_times(|pool, f| pool.schedule_every(Duration::from_millis(40), f).unwrap());
+        for (n, start) in (1..).zip(&starts) {
+            assert!(*start >= Duration::from_millis(40) * n);
+        }
+        // 只检查先后顺序和下界，机器忙的时候每次都可能晚到
+        assert!(starts.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn fixed_delay_waits_after_each_run() {
+        // 间隔 40ms、每次执行 20ms：相邻两次开始之间至少 60ms
+        let starts = start_times(|pool, f| {
+            pool.schedule_with_fixed_delay(Duration::from_millis(40), f)
+                .unwrap()
+        });
+        for pair in starts.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(60));
+        }
+    }
+
+    #[test]
+    fn fixed_rate_never_overlaps() {
+        // 执行时间比周期长时，下一次在上一次结束后才开始
+        let pool = ThreadPool::new(4);
+        let running = Arc::new(AtomicUsize::new(0));
+        let overlapped = Arc::new(AtomicUsize::new(0));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let (r, o, n) = (
+            Arc::clone(&running),
+            Arc::clone(&overlapped),
+            Arc::clone(&runs),
+        );
+        let handle = pool
+            .schedule_every(Duration::from_millis(5), move || {
+                if r.fetch_add(1, Ordering::SeqCst) > 0 {
+                    o.fetch_add(1, Ordering::SeqCst);
+                }
+                thread::sleep(Duration::from_millis(15));
+                r.fetch_sub(1, Ordering::SeqCst);
+                n.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        while runs.load(Ordering::SeqCst) < 4 {
+            thread::sleep(Duration::from_millis(5));
+        }
+        handle.cancel();
+        assert_eq!(overlapped.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn pending_jobs_are_dropped_with_the_pool() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel::<()>();
+        let handle = pool
+            .schedule_after(Duration::from_secs(60), move || tx.send(()).unwrap())
+            .unwrap();
+        drop(pool);
+        assert_eq!(rx.recv(), Err(mpsc::RecvError));
+        handle.cancel();
+    }
+
+    #[test]
+    fn full_queue_never_runs_or_blocks_jobs_on_the_timer_thread() {
+        for policy in [OverflowPolicy::CallerRuns, OverflowPolicy::Block] {
+            let pool = ThreadPool::builder()
+                .num_threads(1)
+                .thread_name("timed")
+                .bounded(1, policy)
+                .build()
+                .unwrap();
+            let (started_tx, started_rx) = mpsc::channel();
+            let (release_tx, release_rx) = mpsc::channel::<()>();
+            pool.execute(move || {
+                started_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+            })
+            .unwrap();
+            started_rx.recv().unwrap();
+            pool.execute(|| {}).unwrap();
+
+            // 队列满着：到期的任务被拒绝，既不在定时器线程上 panic，也不让定时器线程等空位
+            pool.schedule_after(Duration::from_millis(5), || panic!("on the timer thread"))
+                .unwrap();
+            pool.schedule_after(Duration::from_millis(10), || {})
+                .unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while pool.stats().rejected < 2 {
+                assert!(Instant::now() < deadline, "{policy:?}: timer is stuck");
+                thread::sleep(Duration::from_millis(5));
+            }
+
+            release_tx.send(()).unwrap();
+            pool.run_until_idle();
+            let (tx, rx) = mpsc::channel();
+            pool.schedule_after(Duration::from_millis(5), move || {
+                tx.send(thread::current().name().map(String::from)).unwrap()
+            })
+            .unwrap();
+            let ran_on = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(ran_on.as_deref(), Some("timed-0"));
+            assert_eq!(pool.stats().panicked, 0);
+        }
+    }
+}
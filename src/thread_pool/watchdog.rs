@@ -0,0 +1,247 @@
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Job, PoolEvent, Shared};
+
+/// Worker 上正在执行的任务
+struct Running {
+    job_id: u64,
+    label: Option<Arc<str>>,
+    started: Instant,
+    // 每个任务只报告一次
+    reported: bool,
+}
+
+/// 看门狗：记录每个 Worker 当前任务的开始时间，执行超过阈值时发出 StallDetected
+///
+/// 没有设置阈值时什么都不记录，执行任务的路径上不多拿锁。
+pub(crate) struct Watchdog {
+    threshold: Option<Duration>,
+    // 按 Worker id 索引
+    running: Mutex<Vec<Option<Running>>>,
+    state: Mutex<WatchdogState>,
+    wakeup: Condvar,
+}
+
+#[derive(Default)]
+struct WatchdogState {
+    thread: Option<thread::JoinHandle<()>>,
+    shutdown: bool,
+}
+
+/// run_job 期间持有，结束时恢复外层的任务：scope 和 block_on 会在 Worker 上嵌套执行任务
+pub(crate) struct Tracking<'a> {
+    watchdog: &'a Watchdog,
+    worker_id: usize,
+    outer: Option<Running>,
+}
+
+impl Drop for Tracking<'_> {
+    fn drop(&mut self) {
+        let mut running = self.watchdog.running();
+        running[self.worker_id] = self.outer.take();
+    }
+}
+
+impl Watchdog {
+    pub(crate) fn new(threshold: Option<Duration>) -> Watchdog {
+        Watchdog {
+            threshold,
+            running: Mutex::new(Vec::new()),
+            state: Mutex::new(WatchdogState::default()),
+            wakeup: Condvar::new(),
+        }
+    }
+
+    fn running(&self) -> MutexGuard<'_, Vec<Option<Running>>> {
+        self.running.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, WatchdogState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 开始执行 job，返回的 Tracking 被 drop 时视为结束
+    pub(crate) fn track(&self, worker_id: usize, job: &Job) -> Option<Tracking<'_>> {
+        self.threshold?;
+        let mut running = self.running();
+        if running.len() <= worker_id {
+            running.resize_with(worker_id + 1, || None);
+        }
+        let current = Running {
+            job_id: job.id,
+            label: job.label.clone(),
+            started: Instant::now(),
+            reported: false,
+        };
+        let outer = running[worker_id].replace(current);
+        Some(Tracking {
+            watchdog: self,
+            worker_id,
+            outer,
+        })
+    }
+
+    /// 超过阈值、还没报告过的任务，顺便标记为已报告
+    fn stalled(&self, threshold: Duration) -> Vec<PoolEvent> {
+        let mut running = self.running();
+        let mut events = Vec::new();
+        for (worker_id, slot) in running.iter_mut().enumerate() {
+            let Some(job) = slot.as_mut().filter(|job| !job.reported) else {
+                continue;
+            };
+            let elapsed = job.started.elapsed();
+            if elapsed >= threshold {
+                job.reported = true;
+                events.push(PoolEvent::StallDetected {
+                    worker_id,
+                    job_id: job.job_id,
+                    elapsed,
+                    job_label: job.label.as_deref().map(String::from),
+                });
+            }
+        }
+        events
+    }
+
+    /// 停止看门狗线程
+    pub(crate) fn shutdown(&self) {
+        let thread = {
+            let mut state = self.lock();
+            state.shutdown = true;
+            self.wakeup.notify_all();
+            state.thread.take()
+        };
+        if let Some(thread) = thread {
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Shared {
+    /// 设置了阈值时启动看门狗线程，检查间隔是阈值的四分之一
+    pub(crate) fn start_watchdog(self: &Arc<Self>) -> io::Result<()> {
+        let Some(threshold) = self.watchdog.threshold else {
+            return Ok(());
+        };
+        let mut builder = thread::Builder::new();
+        if let Some(prefix) = &self.config.thread_name {
+            builder = builder.name(format!("{prefix}-watchdog"));
+        }
+        let shared = Arc::clone(self);
+        let thread = builder.spawn(move || {
+            let interval = (threshold / 4).clamp(Duration::from_millis(1), Duration::from_secs(1));
+            let watchdog = &shared.watchdog;
+            let mut state = watchdog.lock();
+            while !state.shutdown {
+                drop(state);
+                for event in watchdog.stalled(threshold) {
+                    shared.emit(event);
+                }
+                state = watchdog.lock();
+                if !state.shutdown {
+                    state = watchdog
+                        .wakeup
+                        .wait_timeout(state, interval)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            }
+        })?;
+        self.watchdog.lock().thread = Some(thread);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_pool::{ChannelSink, PoolEvent};
+    use crate::ThreadPool;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn watched_pool(threshold: Duration) -> (ThreadPool, Receiver<PoolEvent>) {
+        let (sink, events) = ChannelSink::new();
+        let pool = ThreadPool::builder()
+            .num_threads(2)
+            .stall_threshold(threshold)
+            .event_sink(sink)
+            .build()
+            .unwrap();
+        (pool, events)
+    }
+
+    fn stalls(events: &Receiver<PoolEvent>) -> Vec<PoolEvent> {
+        events
+            .try_iter()
+            .filter(|event| matches!(event, PoolEvent::StallDetected { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn stalled_job_is_reported_once_with_its_label() {
+        let (pool, events) = watched_pool(Duration::from_millis(20));
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.execute_labeled("nightly import", move || {
+            let _ = release_rx.recv();
+        })
+        .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let stall = loop {
+            let event = events.recv_timeout(deadline - Instant::now()).unwrap();
+            if let PoolEvent::StallDetected { .. } = event {
+                break event;
+            }
+        };
+        let PoolEvent::StallDetected {
+            elapsed, job_label, ..
+        } = &stall
+        else {
+            unreachable!();
+        };
+        assert!(*elapsed >= Duration::from_millis(20));
+        assert_eq!(job_label.as_deref(), Some("nightly import"));
+        assert!(stall.to_string().contains("nightly import"));
+
+        // 同一个任务不会被重复报告
+        thread::sleep(Duration::from_millis(60));
+        assert!(stalls(&events).is_empty());
+        release_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn quick_jobs_are_not_reported() {
+        let (pool, events) = watched_pool(Duration::from_millis(200));
+        let handles: Vec<_> = (0..20)
+            .map(|i| pool.submit_labeled(format!("job {i}"), move || i))
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join(), Ok(i));
+        }
+        drop(pool);
+        assert!(stalls(&events).is_empty());
+    }
+
+    #[test]
+    fn unlabeled_jobs_are_reported_without_label() {
+        let (pool, events) = watched_pool(Duration::from_millis(10));
+        pool.execute(|| thread::sleep(Duration::from_millis(50)))
+            .unwrap();
+        drop(pool);
+        let stalls = stalls(&events);
+        assert_eq!(stalls.len(), 1);
+        assert!(matches!(
+            stalls[0],
+            PoolEvent::StallDetected {
+                job_label: None,
+                ..
+            }
+        ));
+    }
+}
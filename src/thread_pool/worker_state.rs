@@ -0,0 +1,82 @@
+use std::any::{self, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+static NEXT_POOL: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // 按线程池区分：同一个线程可能替不同的线程池执行任务（CallerRuns、确定性模式）
+    static STATES: RefCell<HashMap<u64, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+type Init = Arc<dyn Fn() -> Box<dyn Any> + Send + Sync + 'static>;
+
+/// ThreadPoolBuilder::with_worker_state 设置的每线程状态
+///
+/// 状态放在执行任务的线程的 thread local 里，第一次有任务要用时才调用 init 创建，
+/// 之后同一个 Worker 上的任务拿到的都是同一个值，Worker 退出时随线程一起释放。
+/// 状态从不离开创建它的线程，所以不要求 Send。
+pub(crate) struct WorkerState {
+    pool: u64,
+    type_id: TypeId,
+    type_name: &'static str,
+    init: Init,
+}
+
+/// 任务执行期间把状态借出去，结束或 panic 时放回
+struct Borrowed {
+    pool: u64,
+    state: Option<Box<dyn Any>>,
+}
+
+impl Drop for Borrowed {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            STATES.with(|states| states.borrow_mut().insert(self.pool, state));
+        }
+    }
+}
+
+impl WorkerState {
+    pub(crate) fn new<S, I>(init: I) -> WorkerState
+    where
+        S: 'static,
+        I: Fn() -> S + Send + Sync + 'static,
+    {
+        WorkerState {
+            pool: NEXT_POOL.fetch_add(1, Ordering::Relaxed),
+            type_id: TypeId::of::<S>(),
+            type_name: any::type_name::<S>(),
+            init: Arc::new(move || Box::new(init())),
+        }
+    }
+
+    /// 提交任务时就检查类型，错误在调用方暴露而不是在 Worker 上
+    pub(crate) fn check<S: 'static>(&self) {
+        assert!(
+            self.type_id == TypeId::of::<S>(),
+            "worker state is {}, not {}",
+            self.type_name,
+            any::type_name::<S>()
+        );
+    }
+
+    /// 用当前线程的状态执行 f，还没有就先创建
+    ///
+    /// 借出期间嵌套执行的任务（比如 scope 等待时顺手执行的任务）会拿到一个新建的临时状态。
+    pub(crate) fn with<S: 'static, T>(&self, f: impl FnOnce(&mut S) -> T) -> T {
+        let state = STATES
+            .with(|states| states.borrow_mut().remove(&self.pool))
+            .unwrap_or_else(|| (self.init)());
+        let mut borrowed = Borrowed {
+            pool: self.pool,
+            state: Some(state),
+        };
+        let state = borrowed.state.as_mut().unwrap();
+        f(state
+            .downcast_mut()
+            .expect("worker state has a different type"))
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{self, Read};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use super::{respond, App, Method, ParseError, Request, RequestReader, Response, Status, Version};
+
+/// 空闲等待时多久检查一次有没有别的连接在排队
+const IDLE_POLL: Duration = Duration::from_millis(50);
+
+/// 给每次 read 设置剩余时间的超时，读一个请求的总时间不超过 deadline
+struct Timed {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Read for Timed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            self.stream.set_read_timeout(Some(remaining))?;
+        }
+        self.stream.read(buf)
+    }
+}
+
+/// 在一个连接上依次处理请求，直到客户端关闭、空闲超时、达到请求数上限或者出错
+///
+/// 流水线发来的请求按顺序读取和回复，所以响应的顺序和请求一致。
+pub(super) fn handle_connection(stream: TcpStream, app: &App) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut requests = RequestReader::new(
+        Timed {
+            stream,
+            deadline: None,
+        },
+        app.limits,
+    );
+    let mut served = 0;
+    loop {
+        // 上一个请求之后还没有新数据：空闲等待，有连接在排队时让出 Worker
+        if served > 0
+            && requests.buffered() == 0
+            && !wait_for_request(&requests.get_ref().stream, app)?
+        {
+            return Ok(());
+        }
+        // 从等待第一个字节到读完请求体，整个请求共用一个超时
+        requests.get_mut().deadline = Some(Instant::now() + app.idle_timeout);
+        let request = match requests.next_request() {
+            Ok(Some(request)) => request,
+            // 什么都没发就断开或者一直不发的连接不需要响应
+            Ok(None) | Err(ParseError::UnexpectedEof) | Err(ParseError::Timeout { idle: true }) => {
+                return Ok(());
+            }
+            Err(ParseError::Io(e)) => return Err(e),
+            Err(e) => {
+                let status = e
+                    .status()
+                    .expect("only I/O errors, EOF and idle timeouts have no status");
+                Response::text(status, format!("{e}\n"))
+                    .with_header("Connection", "close")
+                    .write_to(&mut writer)?;
+                lingering_close(writer);
+                return Ok(());
+            }
+        };
+        served += 1;
+
+        let mut response = respond(&request, app);
+        let keep_alive = wants_keep_alive(&request)
+            && served < app.max_requests
+            && !has_token(response.headers.get("Connection"), "close");
+        if keep_alive {
+            response.headers.set("Connection", "keep-alive");
+            // 秒数向上取整：不到一秒的超时写成 timeout=0，客户端会以为连接马上就要关闭
+            response.headers.set(
+                "Keep-Alive",
+                format!(
+                    "timeout={}, max={}",
+                    app.idle_timeout.as_millis().div_ceil(1000),
+                    app.max_requests - served
+                ),
+            );
+        } else {
+            response.headers.set("Connection", "close");
+        }
+        if request.method == Method::Head {
+            response.write_head_to(&mut writer)?;
+        } else {
+            response.write_to(&mut writer)?;
+        }
+        if !keep_alive {
+            // 客户端可能已经流水线发来了更多请求，直接关闭会发出 RST 冲掉最后一个响应
+            lingering_close(writer);
+            return Ok(());
+        }
+    }
+}
+
+/// HTTP/1.1 默认保持连接，HTTP/1.0 要明确要求
+fn wants_keep_alive(request: &Request) -> bool {
+    let connection = request.header("Connection");
+    match request.version {
+        Version::Http11 => !has_token(connection, "close"),
+        Version::Http10 => has_token(connection, "keep-alive"),
+    }
+}
+
+/// Connection 头是逗号分隔、不区分大小写的列表
+fn has_token(header: Option<&str>, token: &str) -> bool {
+    header.is_some_and(|header| {
+        header
+            .split(',')
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    })
+}
+
+/// 等待下一个请求的第一个字节，返回 false 表示应该关闭连接
+///
+/// 超过空闲超时、客户端关闭连接，或者有新连接在排队等 Worker 时都返回 false。
+/// 刚接受的连接在有空闲 Worker 时马上就会被取走，所以连续两次检查都有连接在排队才让出。
+fn wait_for_request(stream: &TcpStream, app: &App) -> io::Result<bool> {
+    let deadline = Instant::now() + app.idle_timeout;
+    let mut probe = [0; 1];
+    let mut saw_waiting = false;
+    loop {
+        let waiting = app.waiting.load(Ordering::SeqCst) > 0;
+        if waiting && saw_waiting {
+            return Ok(false);
+        }
+        saw_waiting = waiting;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(false);
+        }
+        stream.set_read_timeout(Some(remaining.min(IDLE_POLL)))?;
+        match stream.peek(&mut probe) {
+            Ok(0) => return Ok(false),
+            Ok(_) => return Ok(true),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::Interrupted
+                ) => {}
+            // 连接被重置之类的错误说明客户端已经不在了
+            Err(_) => return Ok(false),
+        }
+    }
+}
+
+/// 线程池放不下这个连接：在接受连接的线程上直接回 503，不能等客户端，否则会拖住后面的连接
+pub(super) fn reject_busy(mut stream: TcpStream) {
+    let _ = stream.set_write_timeout(Some(Duration::from_millis(100)));
+    let _ = Response::text(Status::SERVICE_UNAVAILABLE, "server is busy\n")
+        .with_header("Connection", "close")
+        .with_header("Retry-After", "1")
+        .write_to(&mut stream);
+    let _ = stream.shutdown(Shutdown::Write);
+    // 已经到达的请求数据读掉再关闭，否则会发出 RST 冲掉 503；还没到的就不等了
+    if stream.set_nonblocking(true).is_ok() {
+        let mut discard = [0; 4096];
+        let mut total = 0;
+        while total < 64 * 1024 {
+            match stream.read(&mut discard) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => total += n,
+            }
+        }
+    }
+}
+
+/// 关闭前请求可能还没读完，直接关闭会发出 RST，客户端可能来不及读到最后的响应
+///
+/// 先关闭写端，再把客户端已经发来的数据读掉一部分。
+fn lingering_close(mut stream: TcpStream) {
+    let _ = stream.shutdown(Shutdown::Write);
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
+    let mut discard = [0; 4096];
+    let mut total = 0;
+    while total < 256 * 1024 {
+        match stream.read(&mut discard) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => total += n,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web_server::Headers;
+
+    fn request(version: Version, connection: Option<&str>) -> Request {
+        let mut headers = Headers::new();
+        if let Some(connection) = connection {
+            headers.append("Connection", connection);
+        }
+        Request {
+            method: Method::Get,
+            path: "/".to_string(),
+            query: None,
+            version,
+            headers,
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn keep_alive_depends_on_version_and_connection_header() {
+        assert!(wants_keep_alive(&request(Version::Http11, None)));
+        assert!(wants_keep_alive(&request(Version::Http11, Some("Upgrade"))));
+        assert!(!wants_keep_alive(&request(Version::Http11, Some("close"))));
+        assert!(!wants_keep_alive(&request(
+            Version::Http11,
+            Some("TE, Close")
+        )));
+        assert!(!wants_keep_alive(&request(Version::Http10, None)));
+        assert!(wants_keep_alive(&request(
+            Version::Http10,
+            Some("Keep-Alive")
+        )));
+    }
+}
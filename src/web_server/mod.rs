@@ -0,0 +1,175 @@
+/*
+    多线程 Web 服务器：用 ThreadPool 处理每个连接，请求交给 Router 分发，没有匹配的路由时返回 404.html
+
+    连接默认保持（keep-alive），同一个连接上的请求依次处理；空闲的连接在超时或者有新连接排队时关闭，
+    不会一直占着 Worker
+*/
+mod connection;
+mod headers;
+mod request;
+mod response;
+mod router;
+mod static_files;
+
+use std::fs;
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::ThreadPool;
+
+pub use self::headers::Headers;
+pub use self::request::{Limits, Method, ParseError, Request, RequestReader, Version};
+pub use self::response::{Response, Status};
+pub use self::router::{Handler, ParamError, Params, Router};
+pub use self::static_files::StaticFiles;
+
+/// 监听 TCP 端口的服务器，每个连接交给线程池中的一个 Worker 处理
+pub struct Server {
+    listener: TcpListener,
+    pool: ThreadPool,
+    root: PathBuf,
+    limits: Limits,
+    router: Option<Router>,
+    idle_timeout: Duration,
+    max_requests: usize,
+}
+
+/// 所有 Worker 共享的处理请求所需的一切
+struct App {
+    router: Router,
+    root: PathBuf,
+    limits: Limits,
+    idle_timeout: Duration,
+    max_requests: usize,
+    /// 已经接受、还在线程池里排队的连接数
+    waiting: AtomicUsize,
+}
+
+impl Server {
+    /// 绑定地址，页面从当前目录读取，线程池有 4 个 Worker
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            pool: ThreadPool::new(4),
+            root: PathBuf::from("."),
+            limits: Limits::default(),
+            router: None,
+            idle_timeout: Duration::from_secs(5),
+            max_requests: 100,
+        })
+    }
+
+    /// 设置 hello.html 和 404.html 所在的目录
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Server {
+        self.root = root.into();
+        self
+    }
+
+    /// 用 router 分发请求，不设置时只有 GET / 返回 hello.html
+    pub fn router(mut self, router: Router) -> Server {
+        self.router = Some(router);
+        self
+    }
+
+    /// 替换处理连接的线程池
+    pub fn pool(mut self, pool: ThreadPool) -> Server {
+        self.pool = pool;
+        self
+    }
+
+    /// 请求头和请求体的大小上限
+    pub fn limits(mut self, limits: Limits) -> Server {
+        self.limits = limits;
+        self
+    }
+
+    /// 连接空闲多久后关闭，也是读取一个请求的最长时间，默认 5 秒
+    pub fn idle_timeout(mut self, timeout: Duration) -> Server {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// 一个连接最多处理多少个请求，最后一个响应带 Connection: close，默认 100
+    ///
+    /// n 为 0 时按 1 处理。
+    pub fn max_requests_per_connection(mut self, n: usize) -> Server {
+        self.max_requests = n.max(1);
+        self
+    }
+
+    /// 实际监听的地址，绑定端口 0 时用来获取系统分配的端口
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 接受连接直到监听出错
+    ///
+    /// 有界的线程池放不下新连接时回复 503 并关闭它，然后继续接受连接。
+    pub fn run(self) -> io::Result<()> {
+        let router = match self.router {
+            Some(router) => router,
+            None => {
+                let hello = self.root.join("hello.html");
+                Router::new().get("/", move |_, _| html_file(Status::OK, &hello))
+            }
+        };
+        let app = Arc::new(App {
+            router,
+            root: self.root,
+            limits: self.limits,
+            idle_timeout: self.idle_timeout,
+            max_requests: self.max_requests,
+            waiting: AtomicUsize::new(0),
+        });
+        for stream in self.listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                // 客户端在 accept 之前就断开了，不影响后面的连接
+                Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => continue,
+                Err(e) => return Err(e),
+            };
+            // 有界的线程池会连同任务一起丢掉 stream，留一个副本用来回 503
+            let busy = stream.try_clone();
+            let job_app = Arc::clone(&app);
+            app.waiting.fetch_add(1, Ordering::SeqCst);
+            let accepted = self.pool.execute(move || {
+                job_app.waiting.fetch_sub(1, Ordering::SeqCst);
+                if let Err(e) = connection::handle_connection(stream, &job_app) {
+                    eprintln!("connection error: {e}");
+                }
+            });
+            if accepted.is_err() {
+                app.waiting.fetch_sub(1, Ordering::SeqCst);
+                if let Ok(stream) = busy {
+                    connection::reject_busy(stream);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 交给路由处理；没有匹配的路由时返回 404.html，处理函数 panic 时返回 500
+fn respond(request: &Request, app: &App) -> Response {
+    match panic::catch_unwind(AssertUnwindSafe(|| app.router.handle(request))) {
+        Ok(Some(response)) => response,
+        Ok(None) => html_file(Status::NOT_FOUND, &app.root.join("404.html")),
+        Err(_) => Response::text(Status::INTERNAL_SERVER_ERROR, "internal server error\n"),
+    }
+}
+
+/// 读取 HTML 文件作为响应体，读不到时返回 500
+fn html_file(status: Status, path: &Path) -> Response {
+    match fs::read(path) {
+        Ok(body) => Response::html(status, body),
+        Err(e) => {
+            eprintln!("failed to read {}: {e}", path.display());
+            Response::text(Status::INTERNAL_SERVER_ERROR, "internal server error\n")
+        }
+    }
+}
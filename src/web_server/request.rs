@@ -0,0 +1,667 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+use super::{Headers, Status};
+
+/// 请求方法，大小写敏感，不认识的方法放在 Other 里
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Patch,
+    Other(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+            Method::Patch => "PATCH",
+            Method::Other(method) => method,
+        }
+    }
+}
+
+impl From<&str> for Method {
+    fn from(method: &str) -> Method {
+        match method {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "OPTIONS" => Method::Options,
+            "PATCH" => Method::Patch,
+            other => Method::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 支持的 HTTP 版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+        })
+    }
+}
+
+/// 解析好的请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    /// 请求目标中 ? 之前的部分，没有做百分号解码
+    pub path: String,
+    /// ? 之后的部分，不含 ?
+    pub query: Option<String>,
+    pub version: Version,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// 按名字查找请求头，不区分大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+}
+
+/// 请求大小的上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// 请求行加所有请求头的字节数，超过时返回 431
+    pub max_head: usize,
+    /// Content-Length 的最大值，超过时返回 413
+    pub max_body: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_head: 8 * 1024,
+            max_body: 1024 * 1024,
+        }
+    }
+}
+
+/// 读取请求失败的原因
+#[derive(Debug)]
+pub enum ParseError {
+    /// 读取连接出错
+    Io(io::Error),
+    /// 请求还没读完连接就关闭了
+    UnexpectedEof,
+    /// 读取超时；idle 表示这个请求还一个字节都没收到，对应 408 或者直接关闭连接
+    Timeout { idle: bool },
+    /// 格式错误，对应 400
+    Malformed(String),
+    /// 请求头超过 Limits::max_head，对应 431
+    HeadTooLarge,
+    /// Content-Length 超过 Limits::max_body，对应 413
+    BodyTooLarge(u64),
+    /// 不是 HTTP/1.0 或 HTTP/1.1，对应 505
+    UnsupportedVersion(String),
+    /// 带 Transfer-Encoding 的请求体，对应 501
+    UnsupportedTransferEncoding(String),
+}
+
+impl ParseError {
+    /// 应该回复的状态码；连接已经不可用时返回 None
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            ParseError::Io(_) | ParseError::UnexpectedEof => None,
+            ParseError::Timeout { idle: true } => None,
+            ParseError::Timeout { idle: false } => Some(Status::REQUEST_TIMEOUT),
+            ParseError::Malformed(_) => Some(Status::BAD_REQUEST),
+            ParseError::HeadTooLarge => Some(Status::REQUEST_HEADER_FIELDS_TOO_LARGE),
+            ParseError::BodyTooLarge(_) => Some(Status::PAYLOAD_TOO_LARGE),
+            ParseError::UnsupportedVersion(_) => Some(Status::HTTP_VERSION_NOT_SUPPORTED),
+            ParseError::UnsupportedTransferEncoding(_) => Some(Status::NOT_IMPLEMENTED),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "failed to read request: {e}"),
+            ParseError::UnexpectedEof => write!(f, "connection closed in the middle of a request"),
+            ParseError::Timeout { idle: true } => write!(f, "timed out waiting for a request"),
+            ParseError::Timeout { idle: false } => {
+                write!(f, "timed out in the middle of a request")
+            }
+            ParseError::Malformed(reason) => write!(f, "bad request: {reason}"),
+            ParseError::HeadTooLarge => write!(f, "request head is too large"),
+            ParseError::BodyTooLarge(len) => write!(f, "request body of {len} bytes is too large"),
+            ParseError::UnsupportedVersion(version) => {
+                write!(f, "HTTP version {version:?} is not supported")
+            }
+            ParseError::UnsupportedTransferEncoding(encoding) => {
+                write!(f, "transfer encoding {encoding:?} is not supported")
+            }
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        ParseError::Io(e)
+    }
+}
+
+fn malformed(reason: impl Into<String>) -> ParseError {
+    ParseError::Malformed(reason.into())
+}
+
+/// 从任意 Read 中逐个读取请求
+///
+/// 按需读取，多读到的字节留给下一个请求，所以同一个连接上流水线发来的请求也能依次读出。
+pub struct RequestReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    limits: Limits,
+}
+
+impl<R: Read> RequestReader<R> {
+    pub fn new(reader: R, limits: Limits) -> RequestReader<R> {
+        RequestReader {
+            reader,
+            buf: Vec::new(),
+            limits,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// 已经读进来、还没被解析的字节数
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// 读取下一个请求；连接在两个请求之间正常关闭时返回 None
+    ///
+    /// 读取超时（WouldBlock 或 TimedOut）时返回 ParseError::Timeout；请求头还没读完时已读到的部分会保留，
+    /// 可以再次调用继续读。
+    pub fn next_request(&mut self) -> Result<Option<Request>, ParseError> {
+        let head_len = loop {
+            // 请求行之前的空行按 RFC 9112 忽略
+            let blank = self
+                .buf
+                .iter()
+                .take_while(|&&b| b == b'\r' || b == b'\n')
+                .count();
+            self.buf.drain(..blank);
+            if let Some(end) = head_end(&self.buf) {
+                if end > self.limits.max_head {
+                    return Err(ParseError::HeadTooLarge);
+                }
+                break end;
+            }
+            if self.buf.len() >= self.limits.max_head {
+                return Err(ParseError::HeadTooLarge);
+            }
+            if self.fill(self.buf.is_empty())? == 0 {
+                return if self.buf.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(ParseError::UnexpectedEof)
+                };
+            }
+        };
+        let mut request = parse_head(&self.buf[..head_len])?;
+        self.buf.drain(..head_len);
+
+        let body_len = self.body_len(&request.headers)?;
+        while self.buf.len() < body_len {
+            if self.fill(false)? == 0 {
+                return Err(ParseError::UnexpectedEof);
+            }
+        }
+        request.body = self.buf.drain(..body_len).collect();
+        Ok(Some(request))
+    }
+
+    /// 读一次，返回读到的字节数，0 表示连接已关闭；idle 表示当前请求还没有收到任何字节
+    fn fill(&mut self, idle: bool) -> Result<usize, ParseError> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => {
+                    self.buf.extend_from_slice(&chunk[..n]);
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Err(ParseError::Timeout { idle });
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn body_len(&self, headers: &Headers) -> Result<usize, ParseError> {
+        if let Some(encoding) = headers.get("Transfer-Encoding") {
+            return Err(ParseError::UnsupportedTransferEncoding(
+                encoding.to_string(),
+            ));
+        }
+        // 多个 Content-Length（或者逗号分隔的列表）只有全部相同时才接受
+        let mut len = None;
+        for value in headers
+            .get_all("Content-Length")
+            .flat_map(|value| value.split(','))
+        {
+            let value = value.trim();
+            if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(malformed(format!("invalid Content-Length {value:?}")));
+            }
+            // 全是数字却解析失败只可能是溢出
+            let parsed = value.parse::<u64>().unwrap_or(u64::MAX);
+            if len.is_some_and(|len| len != parsed) {
+                return Err(malformed("conflicting Content-Length headers"));
+            }
+            len = Some(parsed);
+        }
+        match len {
+            Some(len) if len > self.limits.max_body as u64 => Err(ParseError::BodyTooLarge(len)),
+            Some(len) => Ok(len as usize),
+            None => Ok(0),
+        }
+    }
+}
+
+/// 请求头结束的位置（空行之后），同时接受 \r\n 和单独的 \n
+fn head_end(buf: &[u8]) -> Option<usize> {
+    buf.iter().enumerate().find_map(|(i, &b)| {
+        if b != b'\n' {
+            return None;
+        }
+        match &buf[i + 1..] {
+            [b'\n', ..] => Some(i + 2),
+            [b'\r', b'\n', ..] => Some(i + 3),
+            _ => None,
+        }
+    })
+}
+
+/// RFC 9110 的 token 字符
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+fn parse_head(head: &[u8]) -> Result<Request, ParseError> {
+    let head = std::str::from_utf8(head).map_err(|_| malformed("request head is not UTF-8"))?;
+    let mut lines = head
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line));
+    let request_line = lines.next().unwrap_or_default();
+
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(malformed(format!(
+            "malformed request line {request_line:?}"
+        )));
+    };
+    if !is_token(method) {
+        return Err(malformed(format!("invalid method {method:?}")));
+    }
+    let method = Method::from(method);
+    let origin_form = target.starts_with('/');
+    let asterisk_form = target == "*" && method == Method::Options;
+    if !(origin_form || asterisk_form) || target.bytes().any(|b| b.is_ascii_control()) {
+        return Err(malformed(format!("invalid request target {target:?}")));
+    }
+    let version = match version {
+        "HTTP/1.0" => Version::Http10,
+        "HTTP/1.1" => Version::Http11,
+        other if other.starts_with("HTTP/") => {
+            return Err(ParseError::UnsupportedVersion(other.to_string()));
+        }
+        other => return Err(malformed(format!("invalid HTTP version {other:?}"))),
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    let mut headers = Headers::new();
+    for line in lines.take_while(|line| !line.is_empty()) {
+        if line.starts_with([' ', '\t']) {
+            return Err(malformed("obsolete header line folding"));
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(malformed(format!("malformed header {line:?}")));
+        };
+        // 冒号前不能有空白，否则按 RFC 9112 必须拒绝
+        if !is_token(name) {
+            return Err(malformed(format!("invalid header name {name:?}")));
+        }
+        let value = value.trim_matches([' ', '\t']);
+        if value.bytes().any(|b| b.is_ascii_control() && b != b'\t') {
+            return Err(malformed(format!("invalid value for header {name}")));
+        }
+        headers.append(name, value);
+    }
+
+    Ok(Request {
+        method,
+        path,
+        query,
+        version,
+        headers,
+        body: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &[u8] =
+        b"POST /submit?x=1&y=2 HTTP/1.1\r\nHost: example.com\r\ncontent-length: 5\r\n\r\nhello";
+
+    fn read_all(raw: &[u8]) -> Result<Option<Request>, ParseError> {
+        RequestReader::new(raw, Limits::default()).next_request()
+    }
+
+    fn status(raw: &[u8], limits: Limits) -> Option<Status> {
+        match RequestReader::new(raw, limits).next_request() {
+            Err(e) => e.status(),
+            Ok(request) => panic!("expected an error, got {request:?}"),
+        }
+    }
+
+    /// 每次 read 只返回一个字节
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Some((&first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    /// xorshift64，测试里生成可复现的随机输入
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    #[test]
+    fn parses_all_parts() {
+        let request = read_all(POST).unwrap().unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.path, "/submit");
+        assert_eq!(request.query.as_deref(), Some("x=1&y=2"));
+        assert_eq!(request.version, Version::Http11);
+        assert_eq!(request.header("HOST"), Some("example.com"));
+        assert_eq!(request.header("Content-Length"), Some("5"));
+        assert_eq!(request.body, b"hello");
+
+        let request = read_all(b"\r\nBREW /pot HTTP/1.0\nX-Empty:\nX-Pad: \t v \t\n\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.method, Method::Other("BREW".to_string()));
+        assert_eq!(request.version, Version::Http10);
+        assert_eq!(request.header("x-empty"), Some(""));
+        assert_eq!(request.header("x-pad"), Some("v"));
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn pipelined_requests_arrive_in_order_even_byte_by_byte() {
+        let raw = [POST, b"GET / HTTP/1.1\r\n\r\n"].concat();
+        let mut whole = RequestReader::new(&raw[..], Limits::default());
+        let mut trickle = RequestReader::new(Trickle(&raw), Limits::default());
+        for _ in 0..2 {
+            let expected = whole.next_request().unwrap().unwrap();
+            assert_eq!(trickle.next_request().unwrap().unwrap(), expected);
+        }
+        assert!(whole.next_request().unwrap().is_none());
+        assert!(trickle.next_request().unwrap().is_none());
+    }
+
+    #[test]
+    fn every_truncation_is_unexpected_eof() {
+        assert!(read_all(b"").unwrap().is_none());
+        assert!(read_all(b"\r\n\r\n").unwrap().is_none());
+        for len in 1..POST.len() {
+            let result =
+                RequestReader::new(Trickle(&POST[..len]), Limits::default()).next_request();
+            assert!(
+                matches!(result, Err(ParseError::UnexpectedEof)),
+                "truncated at {len}: {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_400() {
+        for raw in [
+            &b"GET /\r\n\r\n"[..],
+            b"GET  / HTTP/1.1\r\n\r\n",
+            b"G(ET / HTTP/1.1\r\n\r\n",
+            b"GET index.html HTTP/1.1\r\n\r\n",
+            b"GET * HTTP/1.1\r\n\r\n",
+            b"GET / HTTX/1.1\r\n\r\n",
+            b"GET /\xff HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/1.1\r\nno colon\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost : x\r\n\r\n",
+            b"GET / HTTP/1.1\r\nA: 1\r\n folded\r\n\r\n",
+            b"GET / HTTP/1.1\r\nA: 1\x002\r\n\r\n",
+            b"GET / HTTP/1.1\r\nA: 1\r2\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: +1\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 1, 2\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\n",
+        ] {
+            assert_eq!(
+                status(raw, Limits::default()),
+                Some(Status::BAD_REQUEST),
+                "{}",
+                String::from_utf8_lossy(raw)
+            );
+        }
+        // 重复但相同的 Content-Length 可以接受
+        let same = b"POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 2, 2\r\n\r\nok";
+        assert_eq!(read_all(same).unwrap().unwrap().body, b"ok");
+        assert!(read_all(b"OPTIONS * HTTP/1.1\r\n\r\n").unwrap().is_some());
+    }
+
+    #[test]
+    fn limits_and_unsupported_features() {
+        let limits = Limits {
+            max_head: 64,
+            max_body: 10,
+        };
+        let long_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(64));
+        assert_eq!(
+            status(long_header.as_bytes(), limits),
+            Some(Status::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+        // 没有结尾的超长请求头不会无限读下去
+        let endless = "a".repeat(10_000);
+        assert_eq!(
+            status(endless.as_bytes(), limits),
+            Some(Status::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        );
+        // 请求体还没发过来就能判断超长
+        for len in ["11", "99999999999999999999999"] {
+            let raw = format!("POST / HTTP/1.1\r\nContent-Length: {len}\r\n\r\n");
+            assert_eq!(
+                status(raw.as_bytes(), limits),
+                Some(Status::PAYLOAD_TOO_LARGE)
+            );
+        }
+        let exact = b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\n0123456789";
+        assert!(RequestReader::new(&exact[..], limits)
+            .next_request()
+            .is_ok());
+
+        assert_eq!(
+            status(b"GET / HTTP/2.0\r\n\r\n", limits),
+            Some(Status::HTTP_VERSION_NOT_SUPPORTED)
+        );
+        assert_eq!(
+            status(
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+                limits
+            ),
+            Some(Status::NOT_IMPLEMENTED)
+        );
+    }
+
+    /// 先返回给定的字节，之后一直超时
+    struct Stall<'a>(&'a [u8]);
+
+    impl Read for Stall<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn timeouts_distinguish_idle_from_partial_requests() {
+        let mut idle = RequestReader::new(Stall(b""), Limits::default());
+        let err = idle.next_request().unwrap_err();
+        assert!(matches!(err, ParseError::Timeout { idle: true }));
+        assert_eq!(err.status(), None);
+
+        let mut partial = RequestReader::new(Stall(b"GET / HT"), Limits::default());
+        let err = partial.next_request().unwrap_err();
+        assert!(matches!(err, ParseError::Timeout { idle: false }));
+        assert_eq!(err.status(), Some(Status::REQUEST_TIMEOUT));
+
+        let mut body = RequestReader::new(
+            Stall(b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\n"),
+            Limits::default(),
+        );
+        assert!(matches!(
+            body.next_request(),
+            Err(ParseError::Timeout { idle: false })
+        ));
+
+        // 请求之间超时：前一个请求照常返回
+        let mut between = RequestReader::new(Stall(b"GET / HTTP/1.1\r\n\r\n"), Limits::default());
+        assert!(between.next_request().unwrap().is_some());
+        assert!(matches!(
+            between.next_request(),
+            Err(ParseError::Timeout { idle: true })
+        ));
+    }
+
+    #[test]
+    fn random_garbage_never_panics() {
+        let alphabet = b"GETPOST /?:=&HTTP/1.0\r\n\r\n\t\x00\xffContent-Length 0123456789";
+        let mut rng = Rng(0x5eed);
+        for _ in 0..5000 {
+            let len = rng.below(200);
+            let raw: Vec<u8> = (0..len)
+                .map(|_| alphabet[rng.below(alphabet.len())])
+                .collect();
+            let limits = Limits {
+                max_head: 1 + rng.below(256),
+                max_body: rng.below(64),
+            };
+            let mut reader = RequestReader::new(Trickle(&raw), limits);
+            // 一直读到出错或读完，不能 panic 也不能死循环
+            loop {
+                match reader.next_request() {
+                    Ok(Some(request)) => assert!(request.body.len() <= limits.max_body),
+                    Ok(None) => break,
+                    Err(e) => {
+                        assert!(!matches!(e, ParseError::Io(_)), "{e}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mutated_requests_never_panic() {
+        let mut rng = Rng(42);
+        for _ in 0..5000 {
+            let mut raw = POST.to_vec();
+            for _ in 0..1 + rng.below(4) {
+                let at = rng.below(raw.len());
+                match rng.below(3) {
+                    0 => raw[at] = rng.next() as u8,
+                    1 => {
+                        raw.remove(at);
+                    }
+                    _ => raw.insert(at, rng.next() as u8),
+                }
+            }
+            match read_all(&raw) {
+                Ok(_) | Err(ParseError::UnexpectedEof) => {}
+                Err(e) => assert!(e.status().is_some(), "{e}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use super::Headers;
+
+/// 响应状态码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Status(pub u16);
+
+impl Status {
+    pub const OK: Status = Status(200);
+    pub const NO_CONTENT: Status = Status(204);
+    pub const PARTIAL_CONTENT: Status = Status(206);
+    pub const MOVED_PERMANENTLY: Status = Status(301);
+    pub const NOT_MODIFIED: Status = Status(304);
+    pub const BAD_REQUEST: Status = Status(400);
+    pub const FORBIDDEN: Status = Status(403);
+    pub const NOT_FOUND: Status = Status(404);
+    pub const METHOD_NOT_ALLOWED: Status = Status(405);
+    pub const REQUEST_TIMEOUT: Status = Status(408);
+    pub const PAYLOAD_TOO_LARGE: Status = Status(413);
+    pub const RANGE_NOT_SATISFIABLE: Status = Status(416);
+    pub const REQUEST_HEADER_FIELDS_TOO_LARGE: Status = Status(431);
+    pub const INTERNAL_SERVER_ERROR: Status = Status(500);
+    pub const NOT_IMPLEMENTED: Status = Status(501);
+    pub const SERVICE_UNAVAILABLE: Status = Status(503);
+    pub const HTTP_VERSION_NOT_SUPPORTED: Status = Status(505);
+
+    pub fn code(self) -> u16 {
+        self.0
+    }
+
+    /// 状态行里的原因短语，不认识的状态码返回空串
+    pub fn reason(self) -> &'static str {
+        match self.0 {
+            200 => "OK",
+            204 => "No Content",
+            206 => "Partial Content",
+            301 => "Moved Permanently",
+            304 => "Not Modified",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Content Too Large",
+            416 => "Range Not Satisfiable",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            503 => "Service Unavailable",
+            505 => "HTTP Version Not Supported",
+            _ => "",
+        }
+    }
+
+    /// 1xx、204 和 304 的响应不能带响应体
+    pub fn allows_body(self) -> bool {
+        !(100..200).contains(&self.0) && self != Status::NO_CONTENT && self != Status::NOT_MODIFIED
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.0, self.reason())
+    }
+}
+
+/// HTTP 响应
+///
+/// Content-Length 在写出时按 body 计算，不需要手动设置。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: Status,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// 没有响应体的响应
+    pub fn new(status: Status) -> Response {
+        Response {
+            status,
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn html(status: Status, body: impl Into<Vec<u8>>) -> Response {
+        Response::new(status).with_body("text/html; charset=utf-8", body)
+    }
+
+    pub fn text(status: Status, body: impl Into<Vec<u8>>) -> Response {
+        Response::new(status).with_body("text/plain; charset=utf-8", body)
+    }
+
+    /// 设置响应体和 Content-Type
+    pub fn with_body(mut self, content_type: &str, body: impl Into<Vec<u8>>) -> Response {
+        self.headers.set("Content-Type", content_type);
+        self.body = body.into();
+        self
+    }
+
+    /// 设置头部，替换同名头部
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Response {
+        self.headers.set(name, value);
+        self
+    }
+
+    /// 按 HTTP/1.1 报文格式写出
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        // 头部和响应体一起写，小响应只产生一个 TCP 包
+        let mut message = self.head().into_bytes();
+        if self.status.allows_body() {
+            message.extend_from_slice(&self.body);
+        }
+        writer.write_all(&message)?;
+        writer.flush()
+    }
+
+    /// 只写出状态行和头部，用来回复 HEAD 请求；Content-Length 仍然是响应体的长度
+    pub fn write_head_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.head().as_bytes())?;
+        writer.flush()
+    }
+
+    fn head(&self) -> String {
+        let mut head = format!("HTTP/1.1 {}\r\n{}", self.status, self.headers);
+        if self.status.allows_body() && !self.headers.contains("Content-Length") {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        head.push_str("\r\n");
+        head
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(response: &Response) -> String {
+        let mut out = Vec::new();
+        response.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_status_headers_and_length() {
+        let response = Response::html(Status::NOT_FOUND, "<h1>Oops!</h1>").with_header("X-A", "1");
+        assert_eq!(
+            written(&response),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/html; charset=utf-8\r\nX-A: 1\r\n\
+             Content-Length: 14\r\n\r\n<h1>Oops!</h1>"
+        );
+    }
+
+    #[test]
+    fn bodiless_statuses_have_no_length_or_body() {
+        let response = Response::text(Status::NOT_MODIFIED, "ignored").with_header("ETag", "\"x\"");
+        assert_eq!(
+            written(&response),
+            "HTTP/1.1 304 Not Modified\r\nContent-Type: text/plain; charset=utf-8\r\nETag: \"x\"\r\n\r\n"
+        );
+        assert_eq!(Status(299).to_string(), "299 ");
+
+        let mut head = Vec::new();
+        Response::text(Status::OK, "hello")
+            .write_head_to(&mut head)
+            .unwrap();
+        assert!(head.ends_with(b"Content-Length: 5\r\n\r\n"));
+    }
+}
@@ -0,0 +1,415 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::{Method, Request, Response, Status};
+
+/// 路由处理函数，会被多个 Worker 同时调用
+pub type Handler = Arc<dyn Fn(&Request, &Params) -> Response + Send + Sync + 'static>;
+
+/// 路径模式的一段
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// :name，匹配一段
+    Param(String),
+    /// *name，匹配剩下的所有段，只能放在最后
+    Rest(String),
+}
+
+impl Segment {
+    /// 越小越具体：多个模式都匹配时选最具体的
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Literal(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Rest(_) => 2,
+        }
+    }
+}
+
+struct Route {
+    method: Method,
+    pattern: Vec<Segment>,
+    handler: Handler,
+}
+
+/// 从路径中取出的参数，值已经做过百分号解码
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 把参数解析成 T，比如 params.parse::<u32>("id")
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<T, ParamError> {
+        let value = self
+            .get(name)
+            .ok_or_else(|| ParamError::Missing(name.to_string()))?;
+        value.parse().map_err(|_| ParamError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+}
+
+/// Params::parse 失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamError {
+    /// 模式里没有这个参数
+    Missing(String),
+    /// 值不能解析成要求的类型
+    Invalid { name: String, value: String },
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::Missing(name) => write!(f, "no path parameter named {name:?}"),
+            ParamError::Invalid { name, value } => {
+                write!(f, "invalid value {value:?} for path parameter {name:?}")
+            }
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+/// 按请求方法和路径模式分发请求
+///
+/// 模式由 / 分隔的段组成：普通的段要求完全相同，`:name` 匹配任意一段，`*name` 匹配剩下的所有段（可以没有，/static 和 /static/ 都匹配 /static/*rest）。
+/// 多个模式都匹配时选最具体的（逐段比较，普通段优先于 `:name`，`:name` 优先于 `*name`），
+/// 同样具体时先注册的优先。没有注册 HEAD 的路径用 GET 的处理函数回复 HEAD 请求。
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// 注册处理函数，模式不合法时 panic
+    pub fn route<F>(mut self, method: Method, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            pattern: parse_pattern(pattern),
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn get<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    pub fn post<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    pub fn put<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    pub fn delete<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, &Params) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    /// 处理请求；没有任何模式匹配这个路径时返回 None，由调用方决定 404 的内容
+    ///
+    /// 路径匹配但方法不对时返回 405，Allow 头列出这个路径支持的方法。
+    pub fn handle(&self, request: &Request) -> Option<Response> {
+        let Some(segments) = decode_path(&request.path) else {
+            return Some(Response::text(
+                Status::BAD_REQUEST,
+                "bad request: invalid percent-encoding in path\n",
+            ));
+        };
+        let matched: Vec<(&Route, Params)> = self
+            .routes
+            .iter()
+            .filter_map(|route| Some((route, match_pattern(&route.pattern, &segments)?)))
+            .collect();
+        if matched.is_empty() {
+            return None;
+        }
+
+        let pick = |method: &Method| {
+            matched
+                .iter()
+                .filter(|(route, _)| route.method == *method)
+                .min_by_key(|(route, _)| {
+                    route.pattern.iter().map(Segment::rank).collect::<Vec<_>>()
+                })
+        };
+        let found = pick(&request.method).or_else(|| {
+            (request.method == Method::Head)
+                .then(|| pick(&Method::Get))
+                .flatten()
+        });
+        if let Some((route, params)) = found {
+            return Some((route.handler)(request, params));
+        }
+
+        let mut allow: Vec<&str> = Vec::new();
+        for (route, _) in &matched {
+            let method = route.method.as_str();
+            if !allow.contains(&method) {
+                allow.push(method);
+            }
+            if route.method == Method::Get && !allow.contains(&"HEAD") {
+                allow.push("HEAD");
+            }
+        }
+        Some(
+            Response::text(
+                Status::METHOD_NOT_ALLOWED,
+                format!("method {} is not allowed here\n", request.method),
+            )
+            .with_header("Allow", allow.join(", ")),
+        )
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let Some(rest) = pattern.strip_prefix('/') else {
+        panic!("route pattern {pattern:?} must start with '/'");
+    };
+    let segments: Vec<Segment> = rest
+        .split('/')
+        .map(|segment| {
+            if let Some(name) = segment.strip_prefix(':') {
+                assert!(!name.is_empty(), "unnamed parameter in route {pattern:?}");
+                Segment::Param(name.to_string())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                assert!(!name.is_empty(), "unnamed wildcard in route {pattern:?}");
+                Segment::Rest(name.to_string())
+            } else {
+                Segment::Literal(segment.to_string())
+            }
+        })
+        .collect();
+    for (i, segment) in segments.iter().enumerate() {
+        if let Segment::Rest(name) = segment {
+            assert!(
+                i == segments.len() - 1,
+                "wildcard *{name} must be the last segment of route {pattern:?}"
+            );
+        }
+    }
+    let mut names: Vec<&str> = segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Param(name) | Segment::Rest(name) => Some(name.as_str()),
+            Segment::Literal(_) => None,
+        })
+        .collect();
+    names.sort_unstable();
+    if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        panic!("duplicate parameter {:?} in route {pattern:?}", pair[0]);
+    }
+    segments
+}
+
+/// 按 / 切分后逐段解码，所以 %2F 不会产生新的段
+fn decode_path(path: &str) -> Option<Vec<String>> {
+    path.strip_prefix('/')?
+        .split('/')
+        .map(percent_decode)
+        .collect()
+}
+
+fn match_pattern(pattern: &[Segment], segments: &[String]) -> Option<Params> {
+    let mut params = Vec::new();
+    for (i, segment) in pattern.iter().enumerate() {
+        match segment {
+            Segment::Rest(name) => {
+                params.push((name.clone(), segments.get(i..)?.join("/")));
+                return Some(Params(params));
+            }
+            Segment::Literal(literal) if segments.get(i)? != literal => return None,
+            Segment::Literal(_) => {}
+            Segment::Param(name) => params.push((name.clone(), segments.get(i)?.clone())),
+        }
+    }
+    (segments.len() == pattern.len()).then_some(Params(params))
+}
+
+/// 百分号解码，编码不合法或者解码结果不是 UTF-8 时返回 None
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web_server::{Headers, Version};
+
+    fn request(method: Method, path: &str) -> Request {
+        Request {
+            method,
+            path: path.to_string(),
+            query: None,
+            version: Version::Http11,
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn body(response: Option<Response>) -> (u16, String) {
+        let response = response.expect("route should match");
+        (response.status.0, String::from_utf8(response.body).unwrap())
+    }
+
+    fn echo(name: &'static str) -> impl Fn(&Request, &Params) -> Response + Send + Sync {
+        move |_, params| {
+            let params: Vec<String> = params.iter().map(|(n, v)| format!("{n}={v}")).collect();
+            Response::text(Status::OK, format!("{name} {}", params.join(" ")))
+        }
+    }
+
+    fn router() -> Router {
+        Router::new()
+            .get("/", echo("index"))
+            .get("/users/:id", echo("user"))
+            .get("/users/me", echo("me"))
+            .put("/users/:id", echo("update"))
+            .get("/users/:id/posts/:post", echo("post"))
+            .get("/static/*rest", echo("static"))
+    }
+
+    #[test]
+    fn matches_literals_params_and_wildcards() {
+        let router = router();
+        let get = |path| body(router.handle(&request(Method::Get, path)));
+        assert_eq!(get("/"), (200, "index ".to_string()));
+        assert_eq!(get("/users/42"), (200, "user id=42".to_string()));
+        // 普通段比参数更具体，和注册顺序无关
+        assert_eq!(get("/users/me"), (200, "me ".to_string()));
+        assert_eq!(
+            get("/users/7/posts/9"),
+            (200, "post id=7 post=9".to_string())
+        );
+        assert_eq!(
+            get("/static/css/site.css"),
+            (200, "static rest=css/site.css".to_string())
+        );
+        assert_eq!(get("/static/"), (200, "static rest=".to_string()));
+        // 逐段解码，%2F 留在参数里
+        assert_eq!(get("/users/a%2Fb%20c"), (200, "user id=a/b c".to_string()));
+        assert_eq!(
+            body(router.handle(&request(Method::Put, "/users/42"))),
+            (200, "update id=42".to_string())
+        );
+
+        assert_eq!(get("/static"), (200, "static rest=".to_string()));
+
+        for path in ["/users", "/users/42/", "/stat", "/nope", "/users/1/posts"] {
+            assert!(
+                router.handle(&request(Method::Get, path)).is_none(),
+                "{path}"
+            );
+        }
+        assert_eq!(get("/users/%zz").0, 400);
+    }
+
+    #[test]
+    fn wrong_method_is_405_with_allow() {
+        let router = router();
+        let response = router
+            .handle(&request(Method::Delete, "/users/42"))
+            .unwrap();
+        assert_eq!(response.status, Status::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD, PUT"));
+
+        let response = router.handle(&request(Method::Post, "/")).unwrap();
+        assert_eq!(response.headers.get("Allow"), Some("GET, HEAD"));
+        // HEAD 借用 GET 的处理函数
+        let head = router.handle(&request(Method::Head, "/users/1")).unwrap();
+        assert_eq!(head.status, Status::OK);
+    }
+
+    #[test]
+    fn typed_params() {
+        let router = Router::new().get("/items/:id", |_, params| match params.parse::<u32>("id") {
+            Ok(id) => Response::text(Status::OK, (id * 2).to_string()),
+            Err(e) => Response::text(Status::BAD_REQUEST, e.to_string()),
+        });
+        let get = |path| body(router.handle(&request(Method::Get, path)));
+        assert_eq!(get("/items/21"), (200, "42".to_string()));
+        assert_eq!(
+            get("/items/abc"),
+            (
+                400,
+                "invalid value \"abc\" for path parameter \"id\"".to_string()
+            )
+        );
+        assert_eq!(
+            Params::default().parse::<u32>("id"),
+            Err(ParamError::Missing("id".to_string()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "must be the last segment")]
+    fn wildcard_must_be_last() {
+        let _ = Router::new().get("/files/*path/edit", echo("x"));
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate parameter")]
+    fn duplicate_params_panic() {
+        let _ = Router::new().get("/:id/:id", echo("x"));
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b%2f").as_deref(), Some("a b/"));
+        assert_eq!(percent_decode("%E4%BD%A0").as_deref(), Some("你"));
+        for bad in ["%", "%2", "%g0", "%ff", "%+1"] {
+            assert_eq!(percent_decode(bad), None, "{bad}");
+        }
+    }
+}
@@ -0,0 +1,710 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{Params, Request, Response, Status};
+
+/// 把一个目录作为文档根目录提供静态文件
+///
+/// 支持按扩展名设置 Content-Type、ETag/Last-Modified 条件请求（304）、单个字节范围（206）、
+/// 目录下的 index.html 和可选的目录列表。路径中的 `..` 和指向根目录之外的符号链接都会被拒绝（403）。
+/// 找不到文件时如果根目录下有 404.html 就用它作为响应体。
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    listing: bool,
+}
+
+impl StaticFiles {
+    /// root 必须是已经存在的目录
+    pub fn new(root: impl AsRef<Path>) -> io::Result<StaticFiles> {
+        let root = fs::canonicalize(root)?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        Ok(StaticFiles {
+            root,
+            listing: false,
+        })
+    }
+
+    /// 目录下没有 index.html 时是否列出目录内容，默认不列出（403）
+    pub fn directory_listing(mut self, enabled: bool) -> StaticFiles {
+        self.listing = enabled;
+        self
+    }
+
+    /// 作为路由处理函数，文件路径取自名为 param 的参数，比如 `/static/*path` 里的 path
+    pub fn handler(
+        self,
+        param: &str,
+    ) -> impl Fn(&Request, &Params) -> Response + Send + Sync + 'static {
+        let param = param.to_string();
+        move |request, params| self.serve(request, params.get(&param).unwrap_or(""))
+    }
+
+    /// 按相对于根目录的路径（已经解码，用 / 分隔）回复请求
+    pub fn serve(&self, request: &Request, path: &str) -> Response {
+        let mut resolved = self.root.clone();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => return forbidden(),
+                // 在 Windows 上 \ 和 : 也能跳出当前目录
+                s if s.contains(['\\', ':', '\0']) => return forbidden(),
+                s => resolved.push(s),
+            }
+        }
+        // canonicalize 会解析符号链接，解析后不在根目录下说明链接指向了外面
+        let resolved = match fs::canonicalize(&resolved) {
+            Ok(resolved) if resolved.starts_with(&self.root) => resolved,
+            Ok(_) => return forbidden(),
+            Err(_) => return self.not_found(),
+        };
+        let metadata = match fs::metadata(&resolved) {
+            Ok(metadata) => metadata,
+            Err(_) => return self.not_found(),
+        };
+        if metadata.is_dir() {
+            return self.serve_dir(request, &resolved);
+        }
+        match self.serve_file(request, &resolved, &metadata) {
+            Ok(response) => response,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.not_found(),
+            Err(e) => {
+                eprintln!("failed to read {}: {e}", resolved.display());
+                Response::text(Status::INTERNAL_SERVER_ERROR, "internal server error\n")
+            }
+        }
+    }
+
+    fn not_found(&self) -> Response {
+        match fs::read(self.root.join("404.html")) {
+            Ok(page) => Response::html(Status::NOT_FOUND, page),
+            Err(_) => Response::text(Status::NOT_FOUND, "not found\n"),
+        }
+    }
+
+    fn serve_dir(&self, request: &Request, dir: &Path) -> Response {
+        // 不以 / 结尾时先重定向，否则页面里的相对链接会少一级
+        if !request.path.ends_with('/') {
+            let mut location = format!("{}/", request.path);
+            if let Some(query) = &request.query {
+                location.push('?');
+                location.push_str(query);
+            }
+            return Response::new(Status::MOVED_PERMANENTLY).with_header("Location", location);
+        }
+        let index = dir.join("index.html");
+        if let Ok(metadata) = fs::metadata(&index) {
+            if metadata.is_file() {
+                return self
+                    .serve_file(request, &index, &metadata)
+                    .unwrap_or_else(|_| self.not_found());
+            }
+        }
+        if !self.listing {
+            return forbidden();
+        }
+        match listing(&request.path, dir, dir != self.root) {
+            Ok(page) => Response::html(Status::OK, page),
+            Err(_) => self.not_found(),
+        }
+    }
+
+    fn serve_file(
+        &self,
+        request: &Request,
+        path: &Path,
+        metadata: &fs::Metadata,
+    ) -> io::Result<Response> {
+        let len = metadata.len();
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+        let etag = etag(len, modified);
+        let last_modified = http_date(modified);
+        let validators = |response: Response| {
+            response
+                .with_header("ETag", etag.clone())
+                .with_header("Last-Modified", last_modified.clone())
+        };
+
+        if not_modified(request, &etag, modified) {
+            return Ok(validators(Response::new(Status::NOT_MODIFIED)));
+        }
+
+        let content_type = content_type(path);
+        let range = request
+            .header("Range")
+            .filter(|_| if_range_matches(request, &etag, modified))
+            .and_then(|range| parse_range(range, len));
+        let mut file = File::open(path)?;
+        match range {
+            Some(Ok((start, end))) => {
+                let mut body = vec![0; (end - start + 1) as usize];
+                file.seek(SeekFrom::Start(start))?;
+                file.read_exact(&mut body)?;
+                Ok(validators(
+                    Response::new(Status::PARTIAL_CONTENT)
+                        .with_body(content_type, body)
+                        .with_header("Content-Range", format!("bytes {start}-{end}/{len}")),
+                ))
+            }
+            Some(Err(())) => Ok(Response::new(Status::RANGE_NOT_SATISFIABLE)
+                .with_header("Content-Range", format!("bytes */{len}"))),
+            None => {
+                let mut body = Vec::with_capacity(len as usize);
+                file.read_to_end(&mut body)?;
+                Ok(validators(
+                    Response::new(Status::OK)
+                        .with_body(content_type, body)
+                        .with_header("Accept-Ranges", "bytes"),
+                ))
+            }
+        }
+    }
+}
+
+fn forbidden() -> Response {
+    Response::text(Status::FORBIDDEN, "forbidden\n")
+}
+
+/// 由长度和修改时间生成的强 ETag，文件内容变了两者至少有一个会变
+fn etag(len: u64, modified: SystemTime) -> String {
+    let nanos = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("\"{len:x}-{nanos:x}\"")
+}
+
+/// If-None-Match 优先；没有它时才看 If-Modified-Since
+fn not_modified(request: &Request, etag: &str, modified: SystemTime) -> bool {
+    if let Some(if_none_match) = request.header("If-None-Match") {
+        // 弱比较：W/ 前缀不影响
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+    request
+        .header("If-Modified-Since")
+        .and_then(parse_http_date)
+        .is_some_and(|since| truncate_to_seconds(modified) <= since)
+}
+
+/// 没有 If-Range，或者 If-Range 和当前的 ETag / 修改时间一致时，Range 才生效
+fn if_range_matches(request: &Request, etag: &str, modified: SystemTime) -> bool {
+    match request.header("If-Range") {
+        None => true,
+        Some(tag) if tag.starts_with('"') => tag == etag,
+        Some(date) => parse_http_date(date) == Some(truncate_to_seconds(modified)),
+    }
+}
+
+/// 解析单个字节范围，返回闭区间
+///
+/// 格式不对或者有多个范围时返回 None，按规范忽略 Range 返回整个文件；范围超出文件时返回 Err，对应 416。
+fn parse_range(range: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let range = match (start.trim(), end.trim()) {
+        // bytes=-500：最后 500 个字节
+        ("", suffix) if digits(suffix) => {
+            let suffix: u64 = suffix.parse().unwrap_or(u64::MAX);
+            if suffix == 0 || len == 0 {
+                return Some(Err(()));
+            }
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (start, "") if digits(start) => {
+            let start: u64 = start.parse().unwrap_or(u64::MAX);
+            (start, len.saturating_sub(1))
+        }
+        (start, end) if digits(start) && digits(end) => {
+            let start: u64 = start.parse().unwrap_or(u64::MAX);
+            let end: u64 = end.parse().unwrap_or(u64::MAX);
+            if end < start {
+                return None;
+            }
+            (start, end.min(len.saturating_sub(1)))
+        }
+        _ => return None,
+    };
+    if range.0 >= len {
+        return Some(Err(()));
+    }
+    Some(Ok(range))
+}
+
+/// 按扩展名（不区分大小写）判断 Content-Type
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "rs" | "toml" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// 目录列表页面，目录排在文件前面，各自按名字排序
+fn listing(request_path: &str, dir: &Path, has_parent: bool) -> io::Result<String> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_dir = entry.file_type()?.is_dir();
+        entries.push((!is_dir, entry.file_name().to_string_lossy().into_owned()));
+    }
+    entries.sort();
+
+    let title = html_escape(request_path);
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n    <meta charset=\"utf-8\">\n    \
+         <title>Index of {title}</title>\n</head>\n<body>\n<h1>Index of {title}</h1>\n<ul>\n"
+    );
+    if has_parent {
+        page.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (is_file, name) in entries {
+        let slash = if is_file { "" } else { "/" };
+        let _ = writeln!(
+            page,
+            "<li><a href=\"{}{slash}\">{}{slash}</a></li>",
+            percent_encode(&name),
+            html_escape(&name)
+        );
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+    Ok(page)
+}
+
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 编码链接里的一段路径，只保留 RFC 3986 的非保留字符
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+    }
+    encoded
+}
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// HTTP 日期只精确到秒
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// 1970-01-01 之后的天数转成公历年月日（Howard Hinnant 的 civil_from_days）
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// civil_from_days 的逆运算
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// 格式化成 IMF-fixdate，比如 "Sun, 06 Nov 1994 08:49:37 GMT"
+fn http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// 只接受 IMF-fixdate，其他格式返回 None（按规范忽略这个请求头）
+fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let rest = date.trim().split_once(", ")?.1;
+    let mut parts = rest.split(' ');
+    let (Some(day), Some(month), Some(year), Some(time), Some("GMT"), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return None;
+    };
+    let day: u32 = day.parse().ok().filter(|day| (1..=31).contains(day))?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u32 + 1;
+    // 年份有上限，后面算秒数时才不会溢出
+    let year: i64 = year
+        .parse()
+        .ok()
+        .filter(|year| (1970..=9999).contains(year))?;
+    let mut hms = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(h)), Some(Some(m)), Some(Some(s)), None) =
+        (hms.next(), hms.next(), hms.next(), hms.next())
+    else {
+        return None;
+    };
+    if h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    let secs = (days_from_civil(year, month, day) as u64)
+        .checked_mul(86_400)?
+        .checked_add(h * 3600 + m * 60 + s)?;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web_server::{Headers, Method, Version};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 测试用的临时目录，drop 时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "static-files-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn get(files: &StaticFiles, path: &str, headers: &[(&str, &str)]) -> Response {
+        let mut request_headers = Headers::new();
+        for (name, value) in headers {
+            request_headers.append(*name, *value);
+        }
+        let request = Request {
+            method: Method::Get,
+            path: format!("/{path}"),
+            query: None,
+            version: Version::Http11,
+            headers: request_headers,
+            body: Vec::new(),
+        };
+        files.serve(&request, path)
+    }
+
+    fn site() -> (TempDir, StaticFiles) {
+        let dir = TempDir::new();
+        dir.write("site/hello.html", "<h1>Hello!</h1>");
+        dir.write("site/css/site.CSS", "body {}");
+        dir.write("site/data.bin", "0123456789");
+        dir.write("site/docs/<b>&.txt", "escaped");
+        dir.write("site/docs/sub/index.html", "sub index");
+        dir.write("site/404.html", "<h1>Oops!</h1>");
+        dir.write("secret.txt", "outside the root");
+        let files = StaticFiles::new(dir.0.join("site")).unwrap();
+        (dir, files)
+    }
+
+    #[test]
+    fn serves_files_with_content_type_and_validators() {
+        let (_dir, files) = site();
+        let response = get(&files, "hello.html", &[]);
+        assert_eq!(response.status, Status::OK);
+        assert_eq!(response.body, b"<h1>Hello!</h1>");
+        assert_eq!(
+            response.headers.get("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(response.headers.get("Accept-Ranges"), Some("bytes"));
+        assert!(response.headers.get("ETag").unwrap().starts_with('"'));
+        assert!(response
+            .headers
+            .get("Last-Modified")
+            .unwrap()
+            .ends_with(" GMT"));
+
+        assert_eq!(
+            get(&files, "css/site.CSS", &[]).headers.get("Content-Type"),
+            Some("text/css; charset=utf-8")
+        );
+        assert_eq!(
+            get(&files, "data.bin", &[]).headers.get("Content-Type"),
+            Some("application/octet-stream")
+        );
+        let missing = get(&files, "missing.html", &[]);
+        assert_eq!(missing.status, Status::NOT_FOUND);
+        assert_eq!(missing.body, b"<h1>Oops!</h1>");
+    }
+
+    #[test]
+    fn conditional_requests_get_304() {
+        let (_dir, files) = site();
+        let first = get(&files, "hello.html", &[]);
+        let etag = first.headers.get("ETag").unwrap();
+        let last_modified = first.headers.get("Last-Modified").unwrap();
+
+        let cached = get(&files, "hello.html", &[("If-None-Match", etag)]);
+        assert_eq!(cached.status, Status::NOT_MODIFIED);
+        assert!(cached.body.is_empty());
+        assert_eq!(cached.headers.get("ETag"), Some(etag));
+        let weak = format!("\"other\", W/{etag}");
+        assert_eq!(
+            get(&files, "hello.html", &[("If-None-Match", &weak)]).status,
+            Status::NOT_MODIFIED
+        );
+        assert_eq!(
+            get(&files, "hello.html", &[("If-None-Match", "\"other\"")]).status,
+            Status::OK
+        );
+
+        assert_eq!(
+            get(
+                &files,
+                "hello.html",
+                &[("If-Modified-Since", last_modified)]
+            )
+            .status,
+            Status::NOT_MODIFIED
+        );
+        let old = "Sun, 06 Nov 1994 08:49:37 GMT";
+        assert_eq!(
+            get(&files, "hello.html", &[("If-Modified-Since", old)]).status,
+            Status::OK
+        );
+        // If-None-Match 不匹配时不再看 If-Modified-Since
+        let both = [
+            ("If-None-Match", "\"other\""),
+            ("If-Modified-Since", last_modified),
+        ];
+        assert_eq!(get(&files, "hello.html", &both).status, Status::OK);
+        // 解析不了的日期按规范忽略
+        let far = "Sun, 06 Nov 99999999999999 08:49:37 GMT";
+        assert_eq!(
+            get(&files, "hello.html", &[("If-Modified-Since", far)]).status,
+            Status::OK
+        );
+    }
+
+    #[test]
+    fn single_byte_ranges() {
+        let (_dir, files) = site();
+        let range = |range: &str| {
+            let response = get(&files, "data.bin", &[("Range", range)]);
+            let content_range = response.headers.get("Content-Range").map(String::from);
+            (response.status.0, content_range, response.body)
+        };
+        assert_eq!(
+            range("bytes=2-4"),
+            (206, Some("bytes 2-4/10".to_string()), b"234".to_vec())
+        );
+        assert_eq!(
+            range("bytes=7-"),
+            (206, Some("bytes 7-9/10".to_string()), b"789".to_vec())
+        );
+        assert_eq!(
+            range("bytes=-3"),
+            (206, Some("bytes 7-9/10".to_string()), b"789".to_vec())
+        );
+        assert_eq!(
+            range("bytes=8-100"),
+            (206, Some("bytes 8-9/10".to_string()), b"89".to_vec())
+        );
+        assert_eq!(range("bytes=-100").2, b"0123456789");
+        assert_eq!(
+            range("bytes=10-"),
+            (416, Some("bytes */10".to_string()), Vec::new())
+        );
+        assert_eq!(range("bytes=-0").0, 416);
+        // 多个范围和格式错误的范围都忽略，返回整个文件
+        for ignored in [
+            "bytes=0-1,3-4",
+            "bytes=5-2",
+            "items=0-1",
+            "bytes=a-b",
+            "bytes=-",
+        ] {
+            assert_eq!(
+                range(ignored),
+                (200, None, b"0123456789".to_vec()),
+                "{ignored}"
+            );
+        }
+
+        let etag = get(&files, "data.bin", &[])
+            .headers
+            .get("ETag")
+            .unwrap()
+            .to_string();
+        let fresh = [("Range", "bytes=0-0"), ("If-Range", etag.as_str())];
+        assert_eq!(
+            get(&files, "data.bin", &fresh).status,
+            Status::PARTIAL_CONTENT
+        );
+        let stale = [("Range", "bytes=0-0"), ("If-Range", "\"stale\"")];
+        assert_eq!(get(&files, "data.bin", &stale).status, Status::OK);
+        let far = [
+            ("Range", "bytes=0-0"),
+            ("If-Range", "Sun, 06 Nov 99999999999999 08:49:37 GMT"),
+        ];
+        assert_eq!(get(&files, "data.bin", &far).status, Status::OK);
+    }
+
+    #[test]
+    fn directories_redirect_index_and_listing() {
+        let (_dir, files) = site();
+        let redirect = get(&files, "docs", &[]);
+        assert_eq!(redirect.status, Status::MOVED_PERMANENTLY);
+        assert_eq!(redirect.headers.get("Location"), Some("/docs/"));
+        assert_eq!(get(&files, "docs/sub/", &[]).body, b"sub index");
+        assert_eq!(get(&files, "docs/", &[]).status, Status::FORBIDDEN);
+
+        let files = files.directory_listing(true);
+        let page = String::from_utf8(get(&files, "docs/", &[]).body).unwrap();
+        assert!(page.contains("<a href=\"../\">../</a>"));
+        assert!(page.contains("<a href=\"sub/\">sub/</a>"));
+        assert!(page.contains("<a href=\"%3Cb%3E%26.txt\">&lt;b&gt;&amp;.txt</a>"));
+        // 目录排在文件前面
+        assert!(page.find("sub/").unwrap() < page.find("&lt;b&gt;").unwrap());
+        let root = String::from_utf8(get(&files, "", &[]).body).unwrap();
+        assert!(!root.contains("../"));
+        assert!(root.contains("hello.html"));
+    }
+
+    #[test]
+    fn traversal_is_rejected() {
+        let (_dir, files) = site();
+        for path in ["../secret.txt", "css/../../secret.txt", "..", "a\\..\\b"] {
+            assert_eq!(get(&files, path, &[]).status, Status::FORBIDDEN, "{path}");
+        }
+        // 不越界的 . 段没有问题
+        assert_eq!(get(&files, "./css/./site.CSS", &[]).status, Status::OK);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_may_not_escape_the_root() {
+        use std::os::unix::fs::symlink;
+        let (dir, files) = site();
+        let site = dir.0.join("site");
+        symlink(dir.0.join("secret.txt"), site.join("leak.txt")).unwrap();
+        symlink(&dir.0, site.join("up")).unwrap();
+        symlink(site.join("hello.html"), site.join("alias.html")).unwrap();
+        assert_eq!(get(&files, "leak.txt", &[]).status, Status::FORBIDDEN);
+        assert_eq!(get(&files, "up/secret.txt", &[]).status, Status::FORBIDDEN);
+        // 指向根目录以内的链接照常访问
+        assert_eq!(get(&files, "alias.html", &[]).body, b"<h1>Hello!</h1>");
+    }
+
+    #[test]
+    fn http_dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        let leap = UNIX_EPOCH + Duration::from_secs(951_825_600);
+        assert_eq!(http_date(leap), "Tue, 29 Feb 2000 12:00:00 GMT");
+        assert_eq!(parse_http_date(&http_date(leap)), Some(leap));
+        for bad in [
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 99999999999999 08:49:37 GMT",
+            "Sun, 06 Nov 10000 08:49:37 GMT",
+        ] {
+            assert_eq!(parse_http_date(bad), None, "{bad}");
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_learning::thread_pool::{ChannelSink, OverflowPolicy, PoolEvent};
+use rust_learning::web_server::{Limits, Response, Router, Server, Status};
+use rust_learning::ThreadPool;
+
+const ROOT: &str = env!("CARGO_MANIFEST_DIR");
+
+/// 运行中的 web-server 进程，drop 时结束
+struct Process {
+    child: Child,
+    addr: SocketAddr,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn start_binary(document_root: Option<&str>) -> Process {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_web-server"))
+        .arg("127.0.0.1:0")
+        .args(document_root)
+        .current_dir(ROOT)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // 一直读到进程退出，免得服务器之后写标准输出时管道已经关闭
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let (addr_tx, addr_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in stdout.lines() {
+            let line = line.unwrap();
+            if let Some(addr) = line.strip_prefix("Listening on http://") {
+                let _ = addr_tx.send(addr.parse().unwrap());
+            }
+        }
+    });
+    let addr = addr_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    Process { child, addr }
+}
+
+fn start_in_process(threads: usize) -> SocketAddr {
+    start_with_limits(threads, Limits::default())
+}
+
+fn start_with_limits(threads: usize, limits: Limits) -> SocketAddr {
+    spawn(
+        Server::bind("127.0.0.1:0")
+            .unwrap()
+            .pool(ThreadPool::new(threads))
+            .limits(limits),
+    )
+}
+
+fn spawn(server: Server) -> SocketAddr {
+    let server = server.root(ROOT);
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+    addr
+}
+
+/// 发送原始请求，返回响应头部（不含结尾的空行）和响应体
+fn exchange(addr: SocketAddr, raw: &[u8]) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(raw).unwrap();
+    read_response(&mut stream, raw.starts_with(b"HEAD "))
+}
+
+/// 从连接上读出一个响应，连接保持时不能读到 EOF，只能按 Content-Length 读响应体
+fn read_response(stream: &mut TcpStream, head_only: bool) -> (String, Vec<u8>) {
+    let mut head = Vec::new();
+    let mut byte = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        stream
+            .read_exact(&mut byte)
+            .expect("connection closed before the response head ended");
+        head.push(byte[0]);
+    }
+    head.truncate(head.len() - 4);
+    let head = String::from_utf8(head).unwrap();
+    let status = head.split(' ').nth(1).unwrap();
+    let length = match header(&head, "Content-Length") {
+        _ if head_only || status == "204" || status == "304" => 0,
+        Some(length) => length.parse().unwrap(),
+        None => 0,
+    };
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).unwrap();
+    (head, body)
+}
+
+/// 连接已经被服务器关闭
+fn assert_closed(stream: &mut TcpStream) {
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty(), "unexpected data: {rest:?}");
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (n, v) = line.split_once(": ")?;
+        n.eq_ignore_ascii_case(name).then_some(v)
+    })
+}
+
+/// 发送原始请求，返回状态码和响应体
+fn send(addr: SocketAddr, raw: &[u8]) -> (u16, Vec<u8>) {
+    let (head, body) = exchange(addr, raw);
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    assert!(header(&head, "Content-Length").is_some());
+    (status, body)
+}
+
+fn page(name: &str) -> Vec<u8> {
+    fs::read(Path::new(ROOT).join(name)).unwrap()
+}
+
+#[test]
+fn binary_serves_hello_and_404() {
+    let server = start_binary(None);
+    let get = |target: &str| {
+        send(
+            server.addr,
+            format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes(),
+        )
+    };
+    assert_eq!(get("/"), (200, page("hello.html")));
+    assert_eq!(get("/missing"), (404, page("404.html")));
+    // 查询参数不影响路由
+    assert_eq!(get("/?x=1"), (200, page("hello.html")));
+    let (head, _) = exchange(server.addr, b"POST / HTTP/1.1\r\nContent-Length: 0\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 405 "));
+    assert_eq!(header(&head, "Allow"), Some("GET, HEAD"));
+    let (head, body) = exchange(server.addr, b"HEAD / HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 "));
+    assert_eq!(
+        header(&head, "Content-Length"),
+        Some(page("hello.html").len().to_string().as_str())
+    );
+    assert!(body.is_empty());
+}
+
+#[test]
+fn malformed_request_gets_400() {
+    let addr = start_in_process(2);
+    let (status, body) = send(addr, b"GARBAGE\r\n\r\n");
+    assert_eq!(status, 400);
+    assert!(String::from_utf8(body).unwrap().contains("bad request"));
+    // 服务器还能继续处理正常请求
+    assert_eq!(send(addr, b"GET / HTTP/1.1\r\n\r\n").0, 200);
+}
+
+#[test]
+fn oversized_requests_get_413_and_431() {
+    let addr = start_with_limits(
+        2,
+        Limits {
+            max_head: 256,
+            max_body: 16,
+        },
+    );
+    let long_header = format!("GET / HTTP/1.1\r\nX-Pad: {}\r\n\r\n", "a".repeat(1024));
+    assert_eq!(send(addr, long_header.as_bytes()).0, 431);
+    let body = format!(
+        "POST / HTTP/1.1\r\nContent-Length: 1000\r\n\r\n{}",
+        "b".repeat(1000)
+    );
+    assert_eq!(send(addr, body.as_bytes()).0, 413);
+    assert_eq!(
+        send(
+            addr,
+            b"GET / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"
+        )
+        .0,
+        501
+    );
+    assert_eq!(send(addr, b"GET / HTTP/3\r\n\r\n").0, 505);
+    assert_eq!(
+        send(
+            addr,
+            b"POST / HTTP/1.1\r\nContent-Length: 16\r\n\r\n0123456789abcdef"
+        )
+        .0,
+        405
+    );
+}
+
+#[test]
+fn concurrent_clients_are_all_served() {
+    let addr = start_in_process(4);
+    let clients: Vec<_> = (0..16)
+        .map(|_| thread::spawn(move || send(addr, b"GET / HTTP/1.1\r\n\r\n")))
+        .collect();
+    for client in clients {
+        assert_eq!(client.join().unwrap(), (200, page("hello.html")));
+    }
+}
+
+#[test]
+fn slow_client_does_not_block_others() {
+    let addr = start_in_process(2);
+    // 只发了一半请求头的连接占住一个 Worker
+    let mut slow = TcpStream::connect(addr).unwrap();
+    slow.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+    for _ in 0..5 {
+        assert_eq!(send(addr, b"GET / HTTP/1.1\r\n\r\n").0, 200);
+    }
+    slow.write_all(b"\r\n").unwrap();
+    let (head, _) = read_response(&mut slow, false);
+    assert!(head.starts_with("HTTP/1.1 200 OK"));
+}
+
+#[test]
+fn full_pool_answers_503_and_keeps_accepting() {
+    let (sink, events) = ChannelSink::new();
+    let pool = ThreadPool::builder()
+        .num_threads(1)
+        .bounded(1, OverflowPolicy::Reject)
+        .event_sink(sink)
+        .build()
+        .unwrap();
+    let addr = spawn(Server::bind("127.0.0.1:0").unwrap().pool(pool));
+    // 第一个连接占住唯一的 Worker，等它真正开始执行
+    let mut busy = TcpStream::connect(addr).unwrap();
+    busy.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    busy.write_all(b"GET / HTTP/1.1\r\n").unwrap();
+    loop {
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        if matches!(event, PoolEvent::JobStarted { .. }) {
+            break;
+        }
+    }
+    // 第二个占住队列里唯一的位置：accept 循环按顺序处理连接，
+    // 第三个连接被 accept 时，第二个一定已经进了队列
+    let mut queued = TcpStream::connect(addr).unwrap();
+    queued
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let (head, body) = exchange(addr, b"GET / HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 503 "), "{head}");
+    assert_eq!(header(&head, "Connection"), Some("close"));
+    assert_eq!(body, b"server is busy\n");
+
+    busy.write_all(b"\r\n").unwrap();
+    assert!(read_response(&mut busy, false)
+        .0
+        .starts_with("HTTP/1.1 200 "));
+    queued.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    assert!(read_response(&mut queued, false)
+        .0
+        .starts_with("HTTP/1.1 200 "));
+    drop((busy, queued));
+    assert_eq!(send(addr, b"GET / HTTP/1.1\r\n\r\n").0, 200);
+}
+
+#[test]
+fn router_params_405_and_404_fallback() {
+    let router = Router::new()
+        .get("/users/:id", |_, params| match params.parse::<u32>("id") {
+            Ok(id) => Response::text(Status::OK, format!("user {id}")),
+            Err(e) => Response::text(Status::BAD_REQUEST, e.to_string()),
+        })
+        .post("/users", |request, _| {
+            Response::text(Status::OK, format!("created {} bytes", request.body.len()))
+        })
+        .get("/static/*rest", |_, params| {
+            Response::text(Status::OK, params.get("rest").unwrap().to_string())
+        })
+        .get("/panic", |_, _| panic!("handler bug"));
+    let addr = spawn(Server::bind("127.0.0.1:0").unwrap().router(router));
+
+    assert_eq!(
+        send(addr, b"GET /users/7 HTTP/1.1\r\n\r\n"),
+        (200, b"user 7".to_vec())
+    );
+    assert_eq!(send(addr, b"GET /users/x HTTP/1.1\r\n\r\n").0, 400);
+    assert_eq!(
+        send(
+            addr,
+            b"POST /users HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc"
+        ),
+        (200, b"created 3 bytes".to_vec())
+    );
+    assert_eq!(
+        send(addr, b"GET /static/a/b%20c.txt HTTP/1.1\r\n\r\n"),
+        (200, b"a/b c.txt".to_vec())
+    );
+    let (head, _) = exchange(addr, b"DELETE /users/7 HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 405 "));
+    assert_eq!(header(&head, "Allow"), Some("GET, HEAD"));
+    assert_eq!(
+        send(addr, b"GET / HTTP/1.1\r\n\r\n"),
+        (404, page("404.html"))
+    );
+    assert_eq!(send(addr, b"GET /panic HTTP/1.1\r\n\r\n").0, 500);
+    // 处理函数 panic 之后 Worker 还能继续工作
+    assert_eq!(send(addr, b"GET /users/8 HTTP/1.1\r\n\r\n").0, 200);
+}
+
+#[test]
+fn handlers_run_concurrently() {
+    // 两个请求都进入处理函数之后才一起返回，只有并发执行才能在超时前凑齐
+    let arrived = Arc::new((Mutex::new(0), Condvar::new()));
+    let router = Router::new().get("/wait", move |_, _| {
+        let (count, all_here) = &*arrived;
+        let mut count = count.lock().unwrap();
+        *count += 1;
+        all_here.notify_all();
+        let (count, timeout) = all_here
+            .wait_timeout_while(count, Duration::from_secs(5), |count| *count < 2)
+            .unwrap();
+        drop(count);
+        if timeout.timed_out() {
+            Response::new(Status::REQUEST_TIMEOUT)
+        } else {
+            Response::text(Status::OK, "together")
+        }
+    });
+    let addr = spawn(
+        Server::bind("127.0.0.1:0")
+            .unwrap()
+            .pool(ThreadPool::new(2))
+            .router(router),
+    );
+    let clients: Vec<_> = (0..2)
+        .map(|_| thread::spawn(move || send(addr, b"GET /wait HTTP/1.1\r\n\r\n")))
+        .collect();
+    for client in clients {
+        assert_eq!(client.join().unwrap(), (200, b"together".to_vec()));
+    }
+}
+
+#[test]
+fn binary_serves_a_document_root() {
+    let server = start_binary(Some(ROOT));
+    let (head, body) = exchange(server.addr, b"GET /hello.html HTTP/1.1\r\n\r\n");
+    assert!(head.starts_with("HTTP/1.1 200 "));
+    assert_eq!(
+        header(&head, "Content-Type"),
+        Some("text/html; charset=utf-8")
+    );
+    assert_eq!(body, page("hello.html"));
+
+    let etag = header(&head, "ETag").unwrap();
+    let revalidate = format!("GET /hello.html HTTP/1.1\r\nIf-None-Match: {etag}\r\n\r\n");
+    let (head, body) = exchange(server.addr, revalidate.as_bytes());
+    assert!(head.starts_with("HTTP/1.1 304 "));
+    assert!(body.is_empty());
+
+    let (head, body) = exchange(
+        server.addr,
+        b"GET /hello.html HTTP/1.1\r\nRange: bytes=0-14\r\n\r\n",
+    );
+    assert!(head.starts_with("HTTP/1.1 206 "));
+    assert_eq!(body, page("hello.html")[..15]);
+
+    // 没有 index.html 的目录列出内容
+    let (status, listing) = send(server.addr, b"GET / HTTP/1.1\r\n\r\n");
+    assert_eq!(status, 200);
+    assert!(String::from_utf8(listing).unwrap().contains("hello.html"));
+
+    for traversal in [
+        "/../Cargo.toml",
+        "/src/%2e%2e/%2E%2E/etc/passwd",
+        "/src/..%2f..%2fCargo.toml",
+    ] {
+        let request = format!("GET {traversal} HTTP/1.1\r\n\r\n");
+        assert_eq!(send(server.addr, request.as_bytes()).0, 403, "{traversal}");
+    }
+    assert_eq!(
+        send(server.addr, b"GET /missing.html HTTP/1.1\r\n\r\n"),
+        (404, page("404.html"))
+    );
+}
+
+#[test]
+fn keep_alive_serves_several_requests_on_one_connection() {
+    let addr = start_in_process(2);
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    for _ in 0..3 {
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let (head, body) = read_response(&mut stream, false);
+        assert!(head.starts_with("HTTP/1.1 200 "));
+        assert_eq!(header(&head, "Connection"), Some("keep-alive"));
+        assert_eq!(body, page("hello.html"));
+    }
+    stream.write_all(b"HEAD / HTTP/1.1\r\n\r\n").unwrap();
+    let (head, _) = read_response(&mut stream, true);
+    assert_eq!(header(&head, "Connection"), Some("keep-alive"));
+
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let (head, _) = read_response(&mut stream, false);
+    assert_eq!(header(&head, "Connection"), Some("close"));
+    assert_closed(&mut stream);
+
+    // HTTP/1.0 默认不保持连接
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    let (head, _) = read_response(&mut stream, false);
+    assert_eq!(header(&head, "Connection"), Some("close"));
+    assert_closed(&mut stream);
+}
+
+#[test]
+fn pipelined_requests_are_answered_in_order() {
+    let router = Router::new().get("/sleep/:ms", |_, params| {
+        let ms: u64 = params.parse("ms").unwrap();
+        thread::sleep(Duration::from_millis(ms));
+        Response::text(Status::OK, ms.to_string())
+    });
+    let addr = spawn(
+        Server::bind("127.0.0.1:0")
+            .unwrap()
+            .pool(ThreadPool::new(4))
+            .router(router),
+    );
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(
+            b"GET /sleep/200 HTTP/1.1\r\n\r\n\
+              GET /sleep/0 HTTP/1.1\r\n\r\n\
+              HEAD /sleep/50 HTTP/1.1\r\n\r\n\
+              GET /sleep/10 HTTP/1.1\r\n\r\n",
+        )
+        .unwrap();
+    for (expected, head_only) in [("200", false), ("0", false), ("50", true), ("10", false)] {
+        let (head, body) = read_response(&mut stream, head_only);
+        assert!(head.starts_with("HTTP/1.1 200 "));
+        if head_only {
+            assert_eq!(header(&head, "Content-Length"), Some("2"));
+        } else {
+            assert_eq!(body, expected.as_bytes());
+        }
+    }
+}
+
+#[test]
+fn connection_closes_after_max_requests() {
+    let addr = spawn(
+        Server::bind("127.0.0.1:0")
+            .unwrap()
+            .pool(ThreadPool::new(1))
+            .max_requests_per_connection(2),
+    );
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n")
+        .unwrap();
+    let (head, _) = read_response(&mut stream, false);
+    assert_eq!(header(&head, "Connection"), Some("keep-alive"));
+    assert_eq!(header(&head, "Keep-Alive"), Some("timeout=5, max=1"));
+    let (head, _) = read_response(&mut stream, false);
+    assert_eq!(header(&head, "Connection"), Some("close"));
+    assert_closed(&mut stream);
+}
+
+#[test]
+fn idle_connections_time_out_and_yield_to_waiting_clients() {
+    let addr = spawn(
+        Server::bind("127.0.0.1:0")
+            .unwrap()
+            .pool(ThreadPool::new(1))
+            .idle_timeout(Duration::from_millis(100)),
+    );
+    let mut idle = TcpStream::connect(addr).unwrap();
+    idle.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    idle.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    let (head, _) = read_response(&mut idle, false);
+    // 不到一秒的超时向上取整，不能写成 timeout=0
+    assert_eq!(header(&head, "Keep-Alive"), Some("timeout=1, max=99"));
+    let started = Instant::now();
+    assert_closed(&mut idle);
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    // 只说了一半的请求超时后得到 408
+    let (head, _) = exchange(addr, b"GET / HTTP/1.1\r\n");
+    assert!(head.starts_with("HTTP/1.1 408 "));
+
+    // 唯一的 Worker 被一个空闲的长连接占着，新来的客户端不用等到空闲超时
+    let addr = spawn(
+        Server::bind("127.0.0.1:0")
+            .unwrap()
+            .pool(ThreadPool::new(1))
+            .idle_timeout(Duration::from_secs(10)),
+    );
+    let mut idle = TcpStream::connect(addr).unwrap();
+    idle.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    idle.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+    read_response(&mut idle, false);
+    let started = Instant::now();
+    assert_eq!(send(addr, b"GET / HTTP/1.1\r\n\r\n").0, 200);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_closed(&mut idle);
+}